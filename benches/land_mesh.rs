@@ -0,0 +1,73 @@
+//! Compares the triplicated land mesh, with three vertices per triangle, to
+//! the indexed one sharing cell centres and Voronoi corners.
+//!
+//! Buffer sizes are printed before the timings, as criterion only measures time.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use sailer::geometry::land::{create_indexed_land_geometry, create_land_geometry, Colouring, CornerTable};
+use sailer::util::widgets::biomes::BiomeTable;
+use sailer::util::widgets::climate::{MoistureOpts, TemperatureOpts};
+use sailer::util::widgets::shading::LandShading;
+use sailer::util::widgets::worldgen::WorldGenWidget;
+
+const SUBDIVISIONS: [usize; 3] = [30, 100, 250];
+
+fn land_mesh(c: &mut Criterion) {
+    let biomes = BiomeTable::default();
+    let temperature = TemperatureOpts::default();
+    let moisture = MoistureOpts::default();
+    let colouring = Colouring {
+        biomes: &biomes,
+        temperature: &temperature,
+        moisture: &moisture,
+    };
+
+    let mib = |x: usize| x as f32 / (1024.0 * 1024.0);
+    let mut group = c.benchmark_group("land_mesh");
+    group.sample_size(10);
+
+    for subdivisions in SUBDIVISIONS {
+        let mut gen = WorldGenWidget::default();
+        gen.opts.subdivisions = subdivisions;
+        let world = gen.get_state();
+        let corners = CornerTable::new(&world);
+
+        let flat = create_land_geometry(&world, colouring);
+
+        println!("{} cells", world.positions.len());
+        println!(
+            "  triplicated: {:>9} vertices, {:>8.2} MiB vertex, {:>8.2} MiB index",
+            flat.positions.len(),
+            mib(flat.vertex_bytes()),
+            mib(flat.index_bytes()),
+        );
+        for shading in [LandShading::Flat, LandShading::Smooth] {
+            let indexed = create_indexed_land_geometry(&world, &corners, colouring, shading);
+            println!(
+                "  {:<13}{:>9} vertices, {:>8.2} MiB vertex, {:>8.2} MiB index",
+                format!("{:?}:", shading).to_lowercase(),
+                indexed.positions.len(),
+                mib(indexed.vertex_bytes()),
+                mib(indexed.index_bytes()),
+            );
+        }
+
+        group.bench_with_input(BenchmarkId::new("triplicated", subdivisions), &world, |b, world| {
+            b.iter(|| create_land_geometry(world, colouring))
+        });
+        group.bench_with_input(BenchmarkId::new("indexed_flat", subdivisions), &world, |b, world| {
+            b.iter(|| create_indexed_land_geometry(world, &corners, colouring, LandShading::Flat))
+        });
+        group.bench_with_input(BenchmarkId::new("indexed_smooth", subdivisions), &world, |b, world| {
+            b.iter(|| create_indexed_land_geometry(world, &corners, colouring, LandShading::Smooth))
+        });
+        group.bench_with_input(BenchmarkId::new("corner_table", subdivisions), &world, |b, world| {
+            b.iter(|| CornerTable::new(world))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, land_mesh);
+criterion_main!(benches);
@@ -0,0 +1,188 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use egui::Ui;
+use sphere_terrain::World;
+use crate::snapshot::WorldSnapshot;
+use crate::util::dirty::DirtyCells;
+use crate::util::widgets::worldgen::WorldGenWidget;
+
+/// How often the worker publishes its heights for the land mesh to be rebuilt.
+pub const PUBLISH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Number of chunks a run is split into, bounding how often progress is reported.
+const CHUNKS: usize = 100;
+
+enum Update {
+    Progress(usize),
+    Heights(Vec<f32>),
+    Finished { heights: Vec<f32>, cancelled: bool },
+    Failed(String),
+}
+
+/// What happened to the world since the last poll.
+pub enum ErosionPoll {
+    Idle,
+    Updated,
+    Finished { cancelled: bool },
+    /// The worker stopped without finishing, keeping whatever heights it
+    /// published before
+    Failed(String),
+}
+
+/// Erosion running on a copy of the world on another thread.
+pub struct ErosionJob {
+    pub total: usize,
+    pub done: usize,
+    cancel: Arc<AtomicBool>,
+    receiver: Receiver<Update>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ErosionJob {
+    /// Starts simulating `drops` drops on a copy of `world`, restored through
+    /// `gen` so the copy has the same settings.
+    pub fn spawn(gen: WorldGenWidget, world: &World, drops: usize, blur_iters: usize) -> Self {
+        let snapshot = WorldSnapshot::capture(world, gen.opts.subdivisions);
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+
+        let worker_cancel = cancel.clone();
+        let handle = std::thread::spawn(move || {
+            let mut gen = gen;
+            let mut world = match gen.restore(&snapshot) {
+                Ok(world) => world,
+                Err(e) => {
+                    let _ = sender.send(Update::Failed(format!("could not copy the world: {}", e)));
+                    return;
+                }
+            };
+            let heights = |world: &World| world.heights.iter().map(|x| x.load()).collect::<Vec<_>>();
+
+            let chunk = (drops / CHUNKS).max(1);
+            let mut done = 0;
+            let mut last_publish = Instant::now();
+
+            while done < drops {
+                if worker_cancel.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                let count = chunk.min(drops - done);
+                done += count;
+                // Only blur once the last drops have run.
+                let blur = if done == drops { blur_iters } else { 0 };
+                world.simulate_node_centered_drops(count, blur);
+
+                if sender.send(Update::Progress(done)).is_err() {
+                    return;
+                }
+
+                if done < drops && last_publish.elapsed() >= PUBLISH_INTERVAL {
+                    last_publish = Instant::now();
+                    let _ = sender.send(Update::Heights(heights(&world)));
+                }
+            }
+
+            let _ = sender.send(Update::Finished {
+                heights: heights(&world),
+                cancelled: done < drops,
+            });
+        });
+
+        Self {
+            total: drops,
+            done: 0,
+            cancel,
+            receiver,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn progress(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.done as f32 / self.total as f32
+        }
+    }
+
+    /// Asks the worker to stop after the chunk it is running.
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    /// Copies any heights the worker published into `world`, marking the
+    /// cells that changed in `dirty`.
+    pub fn poll(&mut self, world: &World, dirty: &mut DirtyCells) -> ErosionPoll {
+        let mut result = ErosionPoll::Idle;
+
+        let mut store = |heights: Vec<f32>| {
+            if heights.len() == world.heights.len() {
+                for (idx, (into, x)) in world.heights.iter().zip(heights.into_iter()).enumerate() {
+                    if into.load() != x {
+                        into.store(x);
+                        dirty.mark(idx);
+                    }
+                }
+            }
+        };
+
+        loop {
+            match self.receiver.try_recv() {
+                Ok(Update::Progress(done)) => self.done = done,
+                Ok(Update::Heights(heights)) => {
+                    store(heights);
+                    result = ErosionPoll::Updated;
+                }
+                Ok(Update::Finished { heights, cancelled }) => {
+                    store(heights);
+                    if let Some(handle) = self.handle.take() {
+                        let _ = handle.join();
+                    }
+                    return ErosionPoll::Finished { cancelled };
+                }
+                Ok(Update::Failed(message)) => return self.failed(message),
+                Err(TryRecvError::Empty) => return result,
+                // The worker hung up without finishing, which it only does if it panicked
+                Err(TryRecvError::Disconnected) => {
+                    let message = match self.handle.take().map(JoinHandle::join) {
+                        Some(Err(panic)) => panic
+                            .downcast_ref::<&str>()
+                            .map(|x| x.to_string())
+                            .or_else(|| panic.downcast_ref::<String>().cloned())
+                            .unwrap_or_else(|| "the worker panicked".to_string()),
+                        _ => "the worker stopped without finishing".to_string(),
+                    };
+                    return self.failed(message);
+                }
+            }
+        }
+    }
+
+    fn failed(&mut self, message: String) -> ErosionPoll {
+        println!("Erosion failed after {}/{} drops: {}", self.done, self.total, message);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+
+        ErosionPoll::Failed(message)
+    }
+
+    pub fn render_on(&mut self, ui: &mut Ui) {
+        ui.label(format!("Simulating drops: {}/{}", self.done, self.total));
+        ui.add(egui::ProgressBar::new(self.progress()).show_percentage());
+
+        if ui.button("Cancel").clicked() {
+            self.cancel();
+        }
+    }
+}
+
+impl Drop for ErosionJob {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
@@ -0,0 +1,112 @@
+use std::path::Path;
+use glam::Vec3;
+use serde_json::{json, Value};
+use sphere_terrain::World;
+use crate::rivers::RiverNetwork;
+
+/// Longitude and latitude of `direction` in degrees, in the same projection
+/// as `maps::equirectangular` so the two line up.
+fn lon_lat(direction: Vec3) -> [f32; 2] {
+    let direction = direction.normalize();
+
+    [
+        direction.x.atan2(direction.z).to_degrees(),
+        direction.y.clamp(-1.0, 1.0).asin().to_degrees(),
+    ]
+}
+
+/// Cuts a line wherever it crosses the antimeridian, as RFC 7946 asks, so no
+/// segment runs the long way around the map. Each cut ends one part at ±180°
+/// and starts the next on the other side, at the latitude it crossed at.
+fn split_at_antimeridian(points: &[[f32; 2]]) -> Vec<Vec<[f32; 2]>> {
+    let mut parts = vec![Vec::new()];
+
+    for (idx, &[lon, lat]) in points.iter().enumerate() {
+        if let Some(&[last_lon, last_lat]) = idx.checked_sub(1).map(|x| &points[x]) {
+            let delta = lon - last_lon;
+            if delta.abs() > 180.0 {
+                // Where `lon` would be continuing the same way past the edge
+                let unwrapped = lon - 360.0 * delta.signum();
+                let edge = 180.0 * (unwrapped - last_lon).signum();
+                let t = (edge - last_lon) / (unwrapped - last_lon);
+                let crossing = last_lat + t * (lat - last_lat);
+
+                parts.last_mut().unwrap().push([edge, crossing]);
+                parts.push(vec![[-edge, crossing]]);
+            }
+        }
+
+        parts.last_mut().unwrap().push([lon, lat]);
+    }
+
+    parts
+}
+
+/// Describes every river as a `LineString` feature, from source to mouth,
+/// with the catchment at its mouth and its length in cells. Rivers crossing
+/// the antimeridian become a `MultiLineString` instead.
+pub fn encode_geojson(world: &World, network: &RiverNetwork) -> Value {
+    let features = network
+        .rivers
+        .iter()
+        .map(|river| {
+            let coordinates = river
+                .iter()
+                .map(|&x| lon_lat(world.positions[x]))
+                .collect::<Vec<_>>();
+            let mouth = *river.last().unwrap();
+
+            let mut parts = split_at_antimeridian(&coordinates);
+            let geometry = if parts.len() == 1 {
+                json!({ "type": "LineString", "coordinates": parts.remove(0) })
+            } else {
+                json!({ "type": "MultiLineString", "coordinates": parts })
+            };
+
+            json!({
+                "type": "Feature",
+                "geometry": geometry,
+                "properties": {
+                    "source": river[0],
+                    "mouth": mouth,
+                    "catchment": network.accumulation[mouth],
+                    "cells": river.len(),
+                },
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "type": "FeatureCollection",
+        "features": features,
+    })
+}
+
+pub fn write_geojson(path: impl AsRef<Path>, world: &World, network: &RiverNetwork) -> std::io::Result<()> {
+    let encoded = serde_json::to_vec_pretty(&encode_geojson(world, network))?;
+    std::fs::write(path, encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_are_cut_at_the_antimeridian() {
+        let parts = split_at_antimeridian(&[[170.0, 0.0], [-170.0, 10.0], [-160.0, 10.0]]);
+        assert_eq!(parts, vec![
+            vec![[170.0, 0.0], [180.0, 5.0]],
+            vec![[-180.0, 5.0], [-170.0, 10.0], [-160.0, 10.0]],
+        ]);
+
+        // And the other way around
+        let parts = split_at_antimeridian(&[[-175.0, 0.0], [175.0, -20.0]]);
+        assert_eq!(parts, vec![vec![[-175.0, 0.0], [-180.0, -10.0]], vec![[180.0, -10.0], [175.0, -20.0]]]);
+    }
+
+    #[test]
+    fn lines_away_from_the_antimeridian_stay_whole() {
+        let line = [[-10.0, 0.0], [10.0, 5.0], [170.0, 5.0], [100.0, 0.0]];
+        assert_eq!(split_at_antimeridian(&line), vec![line.to_vec()]);
+    }
+}
@@ -0,0 +1,188 @@
+use std::io::Write;
+use std::path::Path;
+use glam::Vec3;
+use rend3_routine::pbr::{MaterialComponent, PbrMaterial};
+use serde_json::{json, Value};
+use crate::export::{base_colour, right_handed};
+use crate::geometry::land::LandGeometry;
+use crate::util::widgets::material::PbrMaterialWidget;
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_VERSION: u32 = 2;
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+const CHUNK_BIN: u32 = 0x004E_4942;
+
+const ARRAY_BUFFER: u32 = 34962;
+const FLOAT: u32 = 5126;
+const UNSIGNED_BYTE: u32 = 5121;
+
+/// Maps the factors of a land material onto a glTF metallic-roughness material.
+///
+/// Unset factors fall back to the renderer's defaults rather than glTF's.
+fn gltf_material(material: &PbrMaterial) -> Value {
+    let mut out = json!({
+        "name": "land",
+        "pbrMetallicRoughness": {
+            "baseColorFactor": base_colour(material),
+            "metallicFactor": material.metallic_factor.unwrap_or(0.0),
+            "roughnessFactor": material.roughness_factor.unwrap_or(1.0),
+        },
+        "alphaMode": "OPAQUE",
+        "doubleSided": false,
+    });
+
+    if let MaterialComponent::Value(emissive) = material.emissive {
+        out["emissiveFactor"] = json!(emissive.to_array());
+    }
+
+    if material.clearcoat_factor.is_some() || material.clearcoat_roughness_factor.is_some() {
+        out["extensions"] = json!({
+            "KHR_materials_clearcoat": {
+                "clearcoatFactor": material.clearcoat_factor.unwrap_or(0.0),
+                "clearcoatRoughnessFactor": material.clearcoat_roughness_factor.unwrap_or(0.0),
+            }
+        });
+    }
+
+    out
+}
+
+fn pad_to_four(buffer: &mut Vec<u8>, with: u8) {
+    while buffer.len() % 4 != 0 {
+        buffer.push(with);
+    }
+}
+
+/// Encodes the land triangles and material as a binary glTF file.
+///
+/// Positions are stored non-indexed, so every cell keeps its own flat colour
+/// through `COLOR_0`.
+pub fn encode_glb(geometry: &LandGeometry, material: &PbrMaterialWidget) -> Vec<u8> {
+    let LandGeometry { positions, colours } = right_handed(geometry);
+    let count = positions.len();
+
+    let mut min = Vec3::splat(f32::INFINITY);
+    let mut max = Vec3::splat(f32::NEG_INFINITY);
+    positions.iter().for_each(|&x| {
+        min = min.min(x);
+        max = max.max(x);
+    });
+
+    let mut bin = Vec::with_capacity(count * 16);
+    positions
+        .iter()
+        .flat_map(|x| x.to_array())
+        .for_each(|x| bin.extend_from_slice(&x.to_le_bytes()));
+    let positions_len = bin.len();
+    colours.iter().for_each(|x| bin.extend_from_slice(x));
+    let colours_len = bin.len() - positions_len;
+    pad_to_four(&mut bin, 0);
+
+    let material = gltf_material(&material.get_state(true));
+    let uses_clearcoat = material.get("extensions").is_some();
+
+    let mut document = json!({
+        "asset": { "version": "2.0", "generator": "sailer" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0, "name": "land" }],
+        "meshes": [{
+            "name": "land",
+            "primitives": [{
+                "attributes": { "POSITION": 0, "COLOR_0": 1 },
+                "material": 0,
+            }],
+        }],
+        "materials": [material],
+        "buffers": [{ "byteLength": bin.len() }],
+        "bufferViews": [
+            {
+                "buffer": 0,
+                "byteOffset": 0,
+                "byteLength": positions_len,
+                "target": ARRAY_BUFFER,
+            },
+            {
+                "buffer": 0,
+                "byteOffset": positions_len,
+                "byteLength": colours_len,
+                "target": ARRAY_BUFFER,
+            },
+        ],
+        "accessors": [
+            {
+                "bufferView": 0,
+                "componentType": FLOAT,
+                "count": count,
+                "type": "VEC3",
+                "min": min.to_array(),
+                "max": max.to_array(),
+            },
+            {
+                "bufferView": 1,
+                "componentType": UNSIGNED_BYTE,
+                "normalized": true,
+                "count": count,
+                "type": "VEC4",
+            },
+        ],
+    });
+
+    if uses_clearcoat {
+        document["extensionsUsed"] = json!(["KHR_materials_clearcoat"]);
+    }
+
+    let mut json = serde_json::to_vec(&document).unwrap();
+    pad_to_four(&mut json, b' ');
+
+    let total = 12 + 8 + json.len() + 8 + bin.len();
+    let mut out = Vec::with_capacity(total);
+
+    out.extend_from_slice(&GLB_MAGIC.to_le_bytes());
+    out.extend_from_slice(&GLB_VERSION.to_le_bytes());
+    out.extend_from_slice(&(total as u32).to_le_bytes());
+
+    out.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    out.extend_from_slice(&CHUNK_JSON.to_le_bytes());
+    out.extend_from_slice(&json);
+
+    out.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+    out.extend_from_slice(&CHUNK_BIN.to_le_bytes());
+    out.extend_from_slice(&bin);
+
+    out
+}
+
+pub fn write_glb(path: impl AsRef<Path>, geometry: &LandGeometry, material: &PbrMaterialWidget) -> std::io::Result<()> {
+    let mut file = std::fs::File::create(path)?;
+    file.write_all(&encode_glb(geometry, material))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{glb_positions, test_geometry};
+
+    /// Whether the triangle's counter clockwise normal points away from the
+    /// origin, which the land's outward faces do.
+    fn faces_out(triangle: &[Vec3]) -> bool {
+        let normal = (triangle[1] - triangle[0]).cross(triangle[2] - triangle[0]);
+        normal.dot(triangle[0] + triangle[1] + triangle[2]) > 0.0
+    }
+
+    #[test]
+    fn triangles_keep_facing_the_same_way() {
+        let geometry = test_geometry();
+        let exported = glb_positions(&geometry);
+
+        // Mirrored into right handed space...
+        assert_eq!(exported[0], Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(exported[1], Vec3::new(0.0, 1.0, -1.0));
+        assert_eq!(exported[2], Vec3::new(1.0, 0.0, -1.0));
+
+        // ...and rewound, so every face still points the way it did
+        for (original, exported) in geometry.positions.chunks_exact(3).zip(exported.chunks_exact(3)) {
+            assert_eq!(faces_out(original), faces_out(exported));
+        }
+    }
+}
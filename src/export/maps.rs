@@ -0,0 +1,154 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use glam::Vec3;
+use sphere_terrain::World;
+use crate::geometry::land::{cell_colours, nearest_cell, Colouring};
+
+/// A grid of pixels, each holding the index of the cell it falls in.
+pub struct CellRaster {
+    pub width: usize,
+    pub height: usize,
+    pub cells: Vec<usize>,
+}
+
+/// Rasterises cells onto a `width` by `height` grid, where `direction` maps the
+/// centre of a pixel, in the unit square, to a point on the sphere.
+pub fn rasterise(world: &World, width: usize, height: usize, direction: impl Fn(f32, f32) -> Vec3) -> CellRaster {
+    let mut cells = Vec::with_capacity(width * height);
+    let mut row_start = 0;
+
+    for y in 0..height {
+        let mut last = row_start;
+        for x in 0..width {
+            let u = (x as f32 + 0.5) / width as f32;
+            let v = (y as f32 + 0.5) / height as f32;
+            last = nearest_cell(world, direction(u, v).normalize(), last);
+            if x == 0 {
+                row_start = last;
+            }
+            cells.push(last);
+        }
+    }
+
+    CellRaster {
+        width,
+        height,
+        cells,
+    }
+}
+
+/// Longitude/latitude projection around the Y axis, twice as wide as it is tall.
+pub fn equirectangular(world: &World, width: usize) -> CellRaster {
+    rasterise(world, width, (width / 2).max(1), |u, v| {
+        let longitude = u * std::f32::consts::TAU - std::f32::consts::PI;
+        let latitude = std::f32::consts::FRAC_PI_2 - v * std::f32::consts::PI;
+
+        Vec3::new(
+            latitude.cos() * longitude.sin(),
+            latitude.sin(),
+            latitude.cos() * longitude.cos(),
+        )
+    })
+}
+
+/// Suffixes of the cube map faces, in the usual +X, -X, +Y, -Y, +Z, -Z order.
+pub const CUBE_FACES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+/// One face of a cube map, indexed as in [`CUBE_FACES`].
+pub fn cube_face(world: &World, face: usize, size: usize) -> CellRaster {
+    rasterise(world, size, size, |u, v| {
+        let (a, b) = (u * 2.0 - 1.0, 1.0 - v * 2.0);
+
+        match face {
+            0 => Vec3::new(1.0, b, -a),
+            1 => Vec3::new(-1.0, b, a),
+            2 => Vec3::new(a, 1.0, -b),
+            3 => Vec3::new(a, -1.0, b),
+            4 => Vec3::new(a, b, 1.0),
+            _ => Vec3::new(-a, b, -1.0),
+        }
+    })
+}
+
+fn write_png(path: &Path, raster: &CellRaster, colour: png::ColorType, depth: png::BitDepth, data: &[u8]) -> std::io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, raster.width as u32, raster.height as u32);
+    encoder.set_color(colour);
+    encoder.set_depth(depth);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(data)?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Normalises `values` to the full 16 bit range, big endian as PNG expects.
+fn to_gray16(raster: &CellRaster, values: &[f32]) -> Vec<u8> {
+    let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let range = (max - min).max(f32::EPSILON);
+
+    raster
+        .cells
+        .iter()
+        .flat_map(|&x| {
+            let scaled = ((values[x] - min) / range * u16::MAX as f32).round() as u16;
+            scaled.to_be_bytes()
+        })
+        .collect()
+}
+
+/// Writes `<prefix>_height.png` and `<prefix>_wetness.png` as 16 bit grayscale,
+/// and `<prefix>_biome.png` with the biome colour of every cell.
+pub fn write_maps(prefix: &str, world: &World, colouring: Colouring, raster: &CellRaster) -> std::io::Result<()> {
+    let heights = world.heights.iter().map(|x| x.load()).collect::<Vec<_>>();
+    let wetness = world.wetness.iter().map(|x| x.load()).collect::<Vec<_>>();
+    let colours = cell_colours(world, colouring);
+
+    let biome = raster
+        .cells
+        .iter()
+        .flat_map(|&x| {
+            let colour = colours[x] * 255.0;
+            [colour.x as u8, colour.y as u8, colour.z as u8]
+        })
+        .collect::<Vec<_>>();
+
+    write_png(
+        Path::new(&format!("{}_height.png", prefix)),
+        raster,
+        png::ColorType::Grayscale,
+        png::BitDepth::Sixteen,
+        &to_gray16(raster, &heights),
+    )?;
+    write_png(
+        Path::new(&format!("{}_wetness.png", prefix)),
+        raster,
+        png::ColorType::Grayscale,
+        png::BitDepth::Sixteen,
+        &to_gray16(raster, &wetness),
+    )?;
+    write_png(
+        Path::new(&format!("{}_biome.png", prefix)),
+        raster,
+        png::ColorType::Rgb,
+        png::BitDepth::Eight,
+        &biome,
+    )
+}
+
+/// Writes the equirectangular maps to `<prefix>_*.png` and, if `cube_size` is
+/// set, every cube face to `<prefix>_<face>_*.png`.
+pub fn export_maps(prefix: &str, world: &World, colouring: Colouring, width: usize, cube_size: Option<usize>) -> std::io::Result<()> {
+    write_maps(prefix, world, colouring, &equirectangular(world, width))?;
+
+    if let Some(size) = cube_size {
+        for (face, name) in CUBE_FACES.iter().enumerate() {
+            write_maps(&format!("{}_{}", prefix, name), world, colouring, &cube_face(world, face, size))?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use glam::Vec3;
+use rend3_routine::pbr::{AlbedoComponent, PbrMaterial};
+use crate::geometry::land::LandGeometry;
+
+pub mod geojson;
+pub mod glb;
+pub mod maps;
+pub mod obj;
+pub mod ply;
+
+/// Constant albedo of a material, or white if it is textured.
+fn base_colour(material: &PbrMaterial) -> [f32; 4] {
+    match material.albedo {
+        AlbedoComponent::Value(value) | AlbedoComponent::ValueVertex { value, .. } => value.to_array(),
+        _ => [1.0; 4],
+    }
+}
+
+/// The land triangles in the right handed space glTF, Blender and most other
+/// tools use.
+///
+/// The renderer is left handed, so Z is mirrored, and the winding of every
+/// triangle is reversed to keep its front face on the outside.
+fn right_handed(geometry: &LandGeometry) -> LandGeometry {
+    let (positions, colours) = geometry
+        .positions
+        .chunks_exact(3)
+        .zip(geometry.colours.chunks_exact(3))
+        .flat_map(|(positions, colours)| {
+            [0, 2, 1].map(|x| (positions[x] * Vec3::new(1.0, 1.0, -1.0), colours[x]))
+        })
+        .unzip();
+
+    LandGeometry { positions, colours }
+}
+
+/// Two triangles with a different colour on every vertex, for the exporters
+/// to write and read back.
+#[cfg(test)]
+fn test_geometry() -> LandGeometry {
+    LandGeometry {
+        positions: vec![
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(1.0, 0.0, 1.0),
+            Vec3::new(0.0, 1.0, 1.0),
+            Vec3::new(-0.25, 0.5, 1.125),
+            Vec3::new(0.1, -0.3, 0.9),
+            Vec3::new(1e-3, 2.5, -4.0),
+        ],
+        colours: vec![
+            [255, 0, 0, 255],
+            [0, 255, 0, 255],
+            [0, 0, 255, 255],
+            [12, 34, 56, 255],
+            [200, 100, 50, 255],
+            [1, 2, 3, 255],
+        ],
+    }
+}
+
+/// Positions the GLB export of `geometry` holds, which the other exports are
+/// checked against.
+#[cfg(test)]
+fn glb_positions(geometry: &LandGeometry) -> Vec<Vec3> {
+    let glb = glb::encode_glb(geometry, &crate::util::widgets::material::PbrMaterialWidget::default());
+    let u32_at = |at: usize| u32::from_le_bytes(glb[at..at + 4].try_into().unwrap()) as usize;
+    let f32_at = |at: usize| f32::from_le_bytes(glb[at..at + 4].try_into().unwrap());
+
+    // Positions come first in the binary chunk, after the JSON one
+    let bin = 12 + 8 + u32_at(12) + 8;
+    (0..geometry.positions.len())
+        .map(|x| bin + x * 12)
+        .map(|at| Vec3::new(f32_at(at), f32_at(at + 4), f32_at(at + 8)))
+        .collect()
+}
@@ -0,0 +1,118 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use rend3_routine::pbr::MaterialComponent;
+use crate::export::{base_colour, right_handed};
+use crate::geometry::land::LandGeometry;
+use crate::util::widgets::material::PbrMaterialWidget;
+
+/// Writes the land triangles as right handed OBJ, with vertex colours
+/// appended to each `v` line (the extension read by Blender and MeshLab) and
+/// faces referencing material `land` from `mtl_name`.
+pub fn write_obj(mut out: impl Write, geometry: &LandGeometry, mtl_name: &str) -> std::io::Result<()> {
+    let geometry = right_handed(geometry);
+
+    writeln!(out, "mtllib {}", mtl_name)?;
+    writeln!(out, "o land")?;
+
+    for (position, colour) in geometry.positions.iter().zip(geometry.colours.iter()) {
+        writeln!(
+            out,
+            "v {} {} {} {} {} {}",
+            position.x,
+            position.y,
+            position.z,
+            colour[0] as f32 / 255.0,
+            colour[1] as f32 / 255.0,
+            colour[2] as f32 / 255.0,
+        )?;
+    }
+
+    writeln!(out, "usemtl land")?;
+    for face in 0..geometry.positions.len() / 3 {
+        let first = face * 3 + 1;
+        writeln!(out, "f {} {} {}", first, first + 1, first + 2)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the land material as MTL, using the PBR extension keys for metallic,
+/// roughness and clearcoat.
+pub fn write_mtl(mut out: impl Write, material: &PbrMaterialWidget) -> std::io::Result<()> {
+    let material = material.get_state(true);
+    let [r, g, b, a] = base_colour(&material);
+
+    writeln!(out, "newmtl land")?;
+    writeln!(out, "Kd {} {} {}", r, g, b)?;
+    writeln!(out, "d {}", a)?;
+    if let MaterialComponent::Value(emissive) = material.emissive {
+        writeln!(out, "Ke {} {} {}", emissive.x, emissive.y, emissive.z)?;
+    }
+    writeln!(out, "Pm {}", material.metallic_factor.unwrap_or(0.0))?;
+    writeln!(out, "Pr {}", material.roughness_factor.unwrap_or(1.0))?;
+    if let Some(clearcoat) = material.clearcoat_factor {
+        writeln!(out, "Pc {}", clearcoat)?;
+    }
+    if let Some(roughness) = material.clearcoat_roughness_factor {
+        writeln!(out, "Pcr {}", roughness)?;
+    }
+    writeln!(out, "illum 2")?;
+
+    Ok(())
+}
+
+/// Writes `path` and a matching `.mtl` next to it.
+pub fn export_obj(path: impl AsRef<Path>, geometry: &LandGeometry, material: &PbrMaterialWidget) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let mtl_path = path.with_extension("mtl");
+    let mtl_name = mtl_path
+        .file_name()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut obj = BufWriter::new(File::create(path)?);
+    write_obj(&mut obj, geometry, &mtl_name)?;
+    obj.flush()?;
+
+    let mut mtl = BufWriter::new(File::create(&mtl_path)?);
+    write_mtl(&mut mtl, material)?;
+    mtl.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+    use crate::export::{glb_positions, test_geometry};
+
+    #[test]
+    fn reads_back() {
+        let geometry = test_geometry();
+        let mut bytes = Vec::new();
+        write_obj(&mut bytes, &geometry, "land.mtl").unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+
+        let mut positions = Vec::new();
+        let mut colours = Vec::new();
+        let mut faces = Vec::new();
+        for line in text.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("v") => {
+                    let values = words.map(|x| x.parse::<f32>().unwrap()).collect::<Vec<_>>();
+                    positions.push(Vec3::new(values[0], values[1], values[2]));
+                    colours.push([values[3], values[4], values[5]].map(|x| (x * 255.0).round() as u8));
+                }
+                Some("f") => faces.push(words.map(|x| x.parse::<usize>().unwrap()).collect::<Vec<_>>()),
+                _ => {}
+            }
+        }
+
+        // Mirrored and rewound just like the GLB export
+        let expected = right_handed(&geometry);
+        assert_eq!(positions, glb_positions(&geometry));
+        assert_eq!(colours, expected.colours.iter().map(|x| [x[0], x[1], x[2]]).collect::<Vec<_>>());
+        assert_eq!(faces, vec![vec![1, 2, 3], vec![4, 5, 6]]);
+    }
+}
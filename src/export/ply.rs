@@ -0,0 +1,98 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use crate::export::right_handed;
+use crate::geometry::land::LandGeometry;
+
+/// Writes the land triangles as right handed, little endian binary PLY, one
+/// RGBA colour per vertex and one face per three vertices.
+pub fn write_ply(mut out: impl Write, geometry: &LandGeometry) -> std::io::Result<()> {
+    let geometry = right_handed(geometry);
+    let vertices = geometry.positions.len();
+
+    write!(
+        out,
+        "ply\n\
+         format binary_little_endian 1.0\n\
+         element vertex {}\n\
+         property float x\n\
+         property float y\n\
+         property float z\n\
+         property uchar red\n\
+         property uchar green\n\
+         property uchar blue\n\
+         property uchar alpha\n\
+         element face {}\n\
+         property list uchar uint vertex_indices\n\
+         end_header\n",
+        vertices,
+        vertices / 3,
+    )?;
+
+    for (position, colour) in geometry.positions.iter().zip(geometry.colours.iter()) {
+        for x in position.to_array() {
+            out.write_all(&x.to_le_bytes())?;
+        }
+        out.write_all(colour)?;
+    }
+
+    for face in 0..vertices as u32 / 3 {
+        out.write_all(&[3])?;
+        for idx in face * 3..face * 3 + 3 {
+            out.write_all(&idx.to_le_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn export_ply(path: impl AsRef<Path>, geometry: &LandGeometry) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_ply(&mut out, geometry)?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+    use crate::export::{glb_positions, test_geometry};
+
+    #[test]
+    fn reads_back() {
+        let geometry = test_geometry();
+        let mut bytes = Vec::new();
+        write_ply(&mut bytes, &geometry).unwrap();
+
+        let end = b"end_header\n";
+        let body = bytes.windows(end.len()).position(|x| x == end).unwrap() + end.len();
+        let header = std::str::from_utf8(&bytes[..body]).unwrap();
+        assert!(header.contains("element vertex 6\n"));
+        assert!(header.contains("element face 2\n"));
+
+        let f32_at = |at: usize| f32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+
+        // Three floats and four bytes per vertex
+        let (positions, colours): (Vec<_>, Vec<_>) = (0..6)
+            .map(|x| body + x * 16)
+            .map(|at| {
+                let position = Vec3::new(f32_at(at), f32_at(at + 4), f32_at(at + 8));
+                let colour: [u8; 4] = bytes[at + 12..at + 16].try_into().unwrap();
+                (position, colour)
+            })
+            .unzip();
+        // Mirrored and rewound just like the GLB export
+        assert_eq!(positions, glb_positions(&geometry));
+        assert_eq!(colours, right_handed(&geometry).colours);
+
+        // A count and three indices per face
+        let faces = body + 6 * 16;
+        let read = (0..2)
+            .map(|x| faces + x * 13)
+            .map(|at| (bytes[at], [u32_at(at + 1), u32_at(at + 5), u32_at(at + 9)]))
+            .collect::<Vec<_>>();
+        assert_eq!(read, vec![(3, [0, 1, 2]), (3, [3, 4, 5])]);
+        assert_eq!(bytes.len(), faces + 2 * 13);
+    }
+}
@@ -0,0 +1,133 @@
+use glam::{Mat4, Vec3};
+use rend3::types::{MaterialHandle, MeshHandle, Object, ObjectHandle, ObjectMeshKind};
+use rend3::Renderer;
+use rend3_types::{Handedness, Mesh, MeshBuilder};
+use sphere_terrain::{DropSettings, World};
+use crate::util::widgets::climate::TemperatureOpts;
+use crate::util::widgets::clouds::CloudWidget;
+use crate::util::widgets::material::PbrMaterialWidget;
+
+/// Builds a shell of flat cells at `options.altitude`, keeping only the cells
+/// whose noise value lies above the coverage threshold.
+///
+/// Returns `None` if no cell is covered.
+pub fn create_cloud_mesh(options: &CloudWidget) -> Option<Mesh> {
+    let mut shell = World::new(options.opts.subdivisions, DropSettings::default());
+    shell.fill_noise_heights(options.opts.into());
+
+    let range = (options.opts.max - options.opts.min).max(f32::EPSILON);
+    let positions = shell
+        .positions
+        .iter()
+        .map(|&x| x.normalize() * options.altitude)
+        .collect::<Vec<_>>();
+
+    let mut points = Vec::new();
+
+    for (source, &adj) in shell.adjacent.iter().enumerate() {
+        let density = (shell.heights[source].load() - options.opts.min) / range;
+        if density < options.coverage {
+            continue;
+        }
+
+        let mut make = |a, b, c| {
+            let half = positions[source] + positions[b];
+            let pt1: Vec3 = half + positions[a];
+            let pt2: Vec3 = half + positions[c];
+
+            points.extend_from_slice(&[
+                positions[source],
+                pt1.normalize() * options.altitude,
+                pt2.normalize() * options.altitude,
+            ]);
+        };
+
+        for trio in adj.windows(3) {
+            make(trio[0], trio[1], trio[2]);
+        }
+
+        let len = adj.len();
+        make(adj[len - 2], adj[len - 1], adj[0]);
+        make(adj[len - 1], adj[0], adj[1]);
+    }
+
+    if points.is_empty() {
+        return None;
+    }
+
+    Some(
+        MeshBuilder::new(points, Handedness::Left)
+            .build()
+            .unwrap()
+    )
+}
+
+pub struct Clouds {
+    pub material: MaterialHandle,
+    pub mesh: Option<MeshHandle>,
+    pub handle: Option<ObjectHandle>,
+    pub transform: Mat4,
+    pub angle: f32,
+    /// Axis the planet turns around, shared with the temperature's poles
+    pub axis: Vec3,
+}
+
+impl Clouds {
+    pub fn new(renderer: &Renderer, options: &CloudWidget, material: &PbrMaterialWidget, transform: Mat4) -> Self {
+        let mut clouds = Self {
+            material: renderer.add_material(material.get_state(false)),
+            mesh: None,
+            handle: None,
+            transform,
+            angle: 0.0,
+            axis: Vec3::Y,
+        };
+
+        clouds.rebuild(renderer, options);
+
+        clouds
+    }
+
+    fn object_transform(&self) -> Mat4 {
+        self.transform * Mat4::from_axis_angle(self.axis, self.angle)
+    }
+
+    pub fn rebuild(&mut self, renderer: &Renderer, options: &CloudWidget) {
+        self.mesh = create_cloud_mesh(options).map(|mesh| renderer.add_mesh(mesh));
+        self.handle = self.mesh.as_ref().map(|mesh| {
+            renderer.add_object(Object {
+                mesh_kind: ObjectMeshKind::Static(mesh.clone()),
+                material: self.material.clone(),
+                transform: self.object_transform(),
+            })
+        });
+    }
+
+    /// Spins the shell around the planet axis by `elapsed` seconds worth of rotation.
+    pub fn animate(&mut self, renderer: &Renderer, options: &CloudWidget, temperature: &TemperatureOpts, elapsed: f32) {
+        self.axis = temperature.axis();
+        self.angle = (self.angle + options.rotation_speed * elapsed) % std::f32::consts::TAU;
+
+        if let Some(handle) = &self.handle {
+            renderer.set_object_transform(handle, self.object_transform());
+        }
+    }
+
+    pub fn render_on(
+        &mut self,
+        ui: &mut egui::Ui,
+        renderer: &Renderer,
+        material: &mut PbrMaterialWidget,
+        options: &mut CloudWidget,
+    ) {
+        ui.collapsing("Material", |ui| {
+            if let Some(state) = material.render_on(ui, false) {
+                renderer.update_material(&self.material, state);
+            }
+        });
+
+        if options.render_on(ui) {
+            self.rebuild(renderer, options);
+        }
+    }
+}
@@ -1,27 +1,118 @@
+#[cfg(feature = "parallel")]
+use std::cell::Cell;
+use std::collections::HashMap;
 use glam::Vec3;
 use rand::{Rng, SeedableRng};
-use rend3_types::{Handedness, Mesh, MeshBuilder};
 use sphere_terrain::World;
-use crate::util::widgets::palette::Palette;
+use crate::util::dirty::Dirty;
+use crate::util::widgets::biomes::BiomeTable;
+use crate::util::widgets::climate::{MoistureOpts, TemperatureOpts};
+use crate::util::widgets::shading::LandShading;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
-fn make_colour(height: f32, wetness: f32, height_scale: f32, neighbouring: bool, palette: &Palette) -> Vec3 {
+#[cfg(feature = "parallel")]
+thread_local! {
+    /// Runs the serial path on this thread even with the `parallel` feature,
+    /// so tests can compare the two.
+    static SERIAL: Cell<bool> = Cell::new(false);
+}
+
+/// Whether to use the rayon pool.
+#[cfg(feature = "parallel")]
+fn parallel() -> bool {
+    !SERIAL.with(Cell::get)
+}
+
+/// Builds a value for every cell, on the rayon pool with the `parallel`
+/// feature. The bounds are the same either way, so code that builds without
+/// the feature also builds with it.
+fn map_cells<T: Send>(len: usize, f: impl Fn(usize) -> T + Send + Sync) -> Vec<T> {
+    #[cfg(feature = "parallel")]
+    if parallel() {
+        return (0..len).into_par_iter().map(f).collect();
+    }
+
+    (0..len).map(f).collect()
+}
+
+/// Stable sort, on the rayon pool with the `parallel` feature. Both sorts are
+/// stable, so ties come out in the same order either way.
+fn sort_cells<T: Send>(data: &mut [T], compare: impl Fn(&T, &T) -> std::cmp::Ordering + Send + Sync) {
+    #[cfg(feature = "parallel")]
+    if parallel() {
+        data.par_sort_by(compare);
+        return;
+    }
+
+    data.sort_by(compare);
+}
+
+/// Splits `data` into consecutive chunks of `sizes`, one per cell.
+fn split_cells<T>(data: &mut [T], sizes: impl Iterator<Item = usize>) -> Vec<&mut [T]> {
+    let mut rest = data;
+    let mut chunks = Vec::new();
+    for size in sizes {
+        let (chunk, tail) = std::mem::take(&mut rest).split_at_mut(size);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+/// Fills the chunk of every cell with `f`, on the rayon pool with the
+/// `parallel` feature. Every cell writes only to its own chunk, so the output
+/// doesn't depend on the order the cells run in.
+fn fill_cells<T: Send>(data: &mut [T], sizes: impl Iterator<Item = usize>, f: impl Fn(usize, &mut [T]) + Send + Sync) {
+    let chunks = split_cells(data, sizes);
+
+    #[cfg(feature = "parallel")]
+    if parallel() {
+        chunks.into_par_iter().enumerate().for_each(|(idx, chunk)| f(idx, chunk));
+        return;
+    }
+
+    chunks.into_iter().enumerate().for_each(|(idx, chunk)| f(idx, chunk));
+}
+
+/// Everything cell colours depend on besides the world itself.
+#[derive(Copy, Clone)]
+pub struct Colouring<'a> {
+    pub biomes: &'a BiomeTable,
+    pub temperature: &'a TemperatureOpts,
+    pub moisture: &'a MoistureOpts,
+}
+
+fn make_colour(position: Vec3, height: f32, wetness: f32, height_scale: f32, neighbouring: bool, colouring: Colouring) -> Vec3 {
     let height_scale = 1.0 - (1.0 - height_scale).sqrt();
     let wetness = 1.0 - (1.0 - wetness).sqrt();
-    palette.get(wetness, height, height_scale, neighbouring)
+    let coldness = if colouring.temperature.enabled {
+        1.0 - colouring.temperature.temperature(position, height)
+    } else {
+        // Without a climate, highlands stand in for cold places
+        height_scale * height_scale
+    };
+    colouring.biomes.get(wetness, height, coldness, neighbouring)
 }
 
-pub fn create_land_mesh(world: &World, palette: &Palette) -> Mesh {
-    let scaled_positions = world
-        .positions
-        .iter()
-        .copied()
-        .zip(world.heights.iter().map(|x| x.load()))
-        .map(|(x, y)| x * y)
-        // .map(|(x, _)| x)
-        .collect::<Vec<_>>();
-    let mut inner_points = Vec::new();
-    let mut inner_colours = Vec::new();
+/// Flat-shaded land triangles: three vertices per triangle, one colour per cell.
+pub struct LandGeometry {
+    pub positions: Vec<Vec3>,
+    pub colours: Vec<[u8; 4]>,
+}
+
+/// The world-wide normalisation cell colours are computed relative to.
+struct ColourScale {
+    min_height: f32,
+    max_height: f32,
+    wetness_scale: Vec<f32>,
+    /// Wetness of every cell in ascending order, to rank edited cells against
+    sorted_wetness: Vec<f32>,
+    /// Ranked rainfall, when the moisture pass is enabled
+    rainfall_scale: Option<Vec<f32>>,
+}
 
+fn height_range(world: &World) -> (f32, f32) {
     let mut min_height = f32::INFINITY;
     let mut max_height = f32::NEG_INFINITY;
 
@@ -34,61 +125,733 @@ pub fn create_land_mesh(world: &World, palette: &Palette) -> Mesh {
             max_height = max_height.max(loaded);
         });
 
-    let wetness_scale = get_sorted_idx(world.wetness.iter().map(|x| x.load()), |_| true, world.heights.len());
-    let height_scale = get_sorted_idx(world.heights.iter().map(|x| x.load()), |x| x >= 1.0, world.heights.len());
+    (min_height, max_height)
+}
 
-    let mut rng = rand::rngs::StdRng::from_seed([0; 32]);
+/// Rainfall of every cell, when the moisture pass is enabled. Tracing the wind
+/// around the whole planet is slow, so callers that rebuild often keep it.
+fn rainfall(world: &World, colouring: Colouring) -> Option<Vec<f32>> {
+    colouring
+        .moisture
+        .enabled
+        .then(|| colouring.moisture.rainfall(world, colouring.temperature.axis()))
+}
+
+/// Whether rainfall worked out with `old` still holds with `new`.
+fn same_rain(old: Colouring, new: Colouring) -> bool {
+    // The weight only changes how the rainfall is blended in
+    let moisture = MoistureOpts {
+        weight: new.moisture.weight,
+        ..*old.moisture
+    };
 
-    for (source, &adj) in world.adjacent.iter().enumerate() {
-        let neighbouring = adj
+    moisture == *new.moisture && old.temperature.axis() == new.temperature.axis()
+}
+
+impl ColourScale {
+    fn new(world: &World, rainfall: Option<&[f32]>) -> Self {
+        let (min_height, max_height) = height_range(world);
+        let rainfall_scale = rainfall.map(|rainfall| get_sorted_idx(rainfall.iter().copied(), |_| true, world.heights.len()));
+
+        let mut sorted_wetness = world.wetness.iter().map(|x| x.load()).collect::<Vec<_>>();
+        sorted_wetness.sort_unstable_by(f32::total_cmp);
+
+        Self {
+            min_height,
+            max_height,
+            wetness_scale: get_sorted_idx(world.wetness.iter().map(|x| x.load()), |_| true, world.heights.len()),
+            sorted_wetness,
+            rainfall_scale,
+        }
+    }
+
+    /// Whether `height` fits in the range the scale was built for.
+    fn contains(&self, height: f32) -> bool {
+        (self.min_height..=self.max_height).contains(&height)
+    }
+
+    /// Rank of `wetness` among the wetness the scale was built from. Edited
+    /// cells are ranked this way until the next full rebuild ranks them again.
+    fn wetness_rank(&self, wetness: f32) -> f32 {
+        let below = self.sorted_wetness.partition_point(|&x| x < wetness);
+        below as f32 / (self.sorted_wetness.len() - 1) as f32
+    }
+
+    fn colour(&self, world: &World, colouring: Colouring, source: usize) -> Vec3 {
+        let neighbouring = world.adjacent[source]
             .iter()
             .map(|&x| world.heights[x].load() < 1.0)
             .fold(false, |x, y| x | y) | (world.heights[source].load() < 1.0);
         let height = world.heights[source].load();
-        let mut colour = make_colour(
+        let wetness = match &self.rainfall_scale {
+            Some(rainfall) => {
+                let weight = colouring.moisture.weight;
+                self.wetness_scale[source] * (1.0 - weight) + rainfall[source] * weight
+            }
+            None => self.wetness_scale[source],
+        };
+        make_colour(
+            world.positions[source],
             height,
-            wetness_scale[source],
+            wetness,
             // height_scale[source],
-            (height - min_height) / (max_height - min_height),
+            (height - self.min_height) / (self.max_height - self.min_height),
             neighbouring,
-            palette,
-        );
-        colour *= rng.gen_range(0.9..1.0);
-        // let colour = (world.delta_height[source].load() - min_height) / (max_height - min_height);
-        // println!("{}, min: {}, max: {}", colour, min_height, max_height);
-        // let colour = Vec3::splat(colour);
-        let colour = colour * 255.0;
-        let colour = [colour.x as u8, colour.y as u8, colour.z as u8, 255];
-
-        let mut make = |a, b, c| {
-            let half = scaled_positions[source] + scaled_positions[b];
-            let pt1: Vec3 = half + scaled_positions[a];
-            let pt2: Vec3 = half + scaled_positions[c];
-
-            inner_points.extend_from_slice(&[scaled_positions[source], pt1 / 3.0, pt2 / 3.0]);
-            // inner_points.extend_from_slice(&[scaled_positions[source], pt1.normalize(), pt2.normalize()]);
-
-            inner_colours.extend_from_slice(&[colour, colour, colour]);
+            colouring,
+        )
+    }
+}
+
+/// Unjittered biome colour of every cell.
+pub fn cell_colours(world: &World, colouring: Colouring) -> Vec<Vec3> {
+    let scale = ColourScale::new(world, rainfall(world, colouring).as_deref());
+
+    map_cells(world.adjacent.len(), |source| scale.colour(world, colouring, source))
+}
+
+fn scaled_positions(world: &World) -> Vec<Vec3> {
+    map_cells(world.positions.len(), |idx| world.positions[idx] * world.heights[idx].load())
+}
+
+/// Seeded random darkening of every cell, so neighbouring cells of the same
+/// biome stay distinguishable. Always drawn in cell order from a single
+/// generator, so it's the same with or without the `parallel` feature.
+fn jitter(len: usize) -> Vec<f32> {
+    let mut rng = rand::rngs::StdRng::from_seed([0; 32]);
+
+    (0..len).map(|_| rng.gen_range(0.9..1.0)).collect()
+}
+
+fn to_vertex_colour(colour: Vec3, jitter: f32) -> [u8; 4] {
+    let mut colour = colour;
+    colour *= jitter;
+    let colour = colour * 255.0;
+    [colour.x as u8, colour.y as u8, colour.z as u8, 255]
+}
+
+/// Cell colours with the seeded jitter applied, ready to be used as vertex colours.
+pub fn jittered_colours(world: &World, colouring: Colouring) -> Vec<[u8; 4]> {
+    let colours = cell_colours(world, colouring);
+    let jitter = jitter(world.adjacent.len());
+
+    map_cells(colours.len(), |idx| to_vertex_colour(colours[idx], jitter[idx]))
+}
+
+pub fn create_land_geometry(world: &World, colouring: Colouring) -> LandGeometry {
+    let scaled_positions = scaled_positions(world);
+    let colours = jittered_colours(world, colouring);
+
+    let vertices = world.adjacent.iter().map(|adj| adj.len() * 3).sum::<usize>();
+    let mut inner_points = vec![Vec3::ZERO; vertices];
+    let mut inner_colours = vec![[0; 4]; vertices];
+
+    fill_cells(
+        &mut inner_points,
+        world.adjacent.iter().map(|adj| adj.len() * 3),
+        |source, out| {
+            let adj = &world.adjacent[source];
+            let len = adj.len();
+
+            // One triangle per trio of consecutive neighbours, wrapping around
+            for (t, out) in out.chunks_exact_mut(3).enumerate() {
+                let (a, b, c) = (adj[t], adj[(t + 1) % len], adj[(t + 2) % len]);
+
+                let half = scaled_positions[source] + scaled_positions[b];
+                let pt1: Vec3 = half + scaled_positions[a];
+                let pt2: Vec3 = half + scaled_positions[c];
+
+                out.copy_from_slice(&[scaled_positions[source], pt1 / 3.0, pt2 / 3.0]);
+                // out.copy_from_slice(&[scaled_positions[source], pt1.normalize(), pt2.normalize()]);
+            }
+        },
+    );
+
+    fill_cells(
+        &mut inner_colours,
+        world.adjacent.iter().map(|adj| adj.len() * 3),
+        |source, out| out.fill(colours[source]),
+    );
+
+    LandGeometry {
+        positions: inner_points,
+        colours: inner_colours,
+    }
+}
+
+impl LandGeometry {
+    /// Bytes of vertex data, as uploaded to the GPU. rend3 adds a normal to
+    /// every vertex of a mesh built without them.
+    pub fn vertex_bytes(&self) -> usize {
+        self.positions.len() * VERTEX_BYTES
+    }
+
+    /// Bytes of index data. rend3 counts through the vertices of a mesh built
+    /// without indices, so there's still one index per vertex.
+    pub fn index_bytes(&self) -> usize {
+        self.positions.len() * std::mem::size_of::<u32>()
+    }
+}
+
+/// Bytes of a position, normal and colour.
+const VERTEX_BYTES: usize = 2 * std::mem::size_of::<Vec3>() + std::mem::size_of::<[u8; 4]>();
+
+/// Finds the cell nearest to `direction` by walking the adjacency graph from
+/// `start`. The cell centres are a Delaunay triangulation of the sphere, so a
+/// greedy walk always ends at the closest one.
+pub fn nearest_cell(world: &World, direction: Vec3, start: usize) -> usize {
+    let mut current = start;
+    let mut best = world.positions[current].normalize().dot(direction);
+
+    loop {
+        let next = world.adjacent[current]
+            .iter()
+            .map(|&x| (x, world.positions[x].normalize().dot(direction)))
+            .max_by(|x, y| x.1.total_cmp(&y.1));
+
+        match next {
+            Some((idx, dot)) if dot > best => {
+                current = idx;
+                best = dot;
+            }
+            _ => return current,
+        }
+    }
+}
+
+/// The Voronoi corners of a world, each being where three cells meet.
+pub struct CornerTable {
+    /// The cells meeting at every corner, sorted
+    pub cells: Vec<[u32; 3]>,
+    /// Corners around every cell, corner `k` lying between neighbours `k` and `k + 1`
+    rings: Vec<u32>,
+    /// Where the ring of every cell starts in `rings`, and where the last one ends
+    offsets: Vec<u32>,
+}
+
+impl CornerTable {
+    /// Numbers the corners in the order the cells first reach them, so the
+    /// table only depends on the world's topology.
+    pub fn new(world: &World) -> Self {
+        let mut ids = HashMap::new();
+        let mut cells = Vec::new();
+        let mut rings = Vec::new();
+        let mut offsets = Vec::with_capacity(world.adjacent.len() + 1);
+        offsets.push(0);
+
+        for (source, adj) in world.adjacent.iter().enumerate() {
+            let len = adj.len();
+
+            for k in 0..len {
+                let mut key = [source, adj[k], adj[(k + 1) % len]].map(|x| x as u32);
+                key.sort_unstable();
+
+                let id = *ids.entry(key).or_insert_with(|| {
+                    cells.push(key);
+                    cells.len() as u32 - 1
+                });
+                rings.push(id);
+            }
+
+            offsets.push(rings.len() as u32);
+        }
+
+        Self { cells, rings, offsets }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn ring(&self, cell: usize) -> &[u32] {
+        &self.rings[self.offsets[cell] as usize..self.offsets[cell + 1] as usize]
+    }
+
+    /// Number of cells the table was built for.
+    fn cells(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Number of vertices of an [`IndexedLandGeometry`] drawn with `shading`.
+    pub fn vertices(&self, shading: LandShading) -> usize {
+        match shading {
+            LandShading::Flat => self.cells() + self.rings.len(),
+            LandShading::Smooth => self.cells() + self.len(),
+        }
+    }
+
+    /// Vertex of [`IndexedLandGeometry`] in the centre of `cell`. Flat cells
+    /// have their own copy of their corners straight after it.
+    fn centre_vertex(&self, cell: usize, shading: LandShading) -> usize {
+        match shading {
+            LandShading::Flat => cell + self.offsets[cell] as usize,
+            LandShading::Smooth => cell,
+        }
+    }
+
+    /// Vertex of [`IndexedLandGeometry`] for corner `k` around `cell`.
+    fn corner_vertex(&self, cell: usize, k: usize, shading: LandShading) -> usize {
+        match shading {
+            LandShading::Flat => self.centre_vertex(cell, shading) + 1 + k,
+            LandShading::Smooth => self.cells() + self.ring(cell)[k] as usize,
+        }
+    }
+
+    /// Triangles of `cell` as vertices of [`IndexedLandGeometry`], fanning out
+    /// from its centre with one triangle per pair of consecutive corners.
+    pub fn triangles(&self, cell: usize, shading: LandShading) -> impl Iterator<Item = [u32; 3]> + '_ {
+        let len = self.ring(cell).len();
+        let centre = self.centre_vertex(cell, shading) as u32;
+
+        (0..len).map(move |k| {
+            [
+                centre,
+                self.corner_vertex(cell, k, shading) as u32,
+                self.corner_vertex(cell, (k + 1) % len, shading) as u32,
+            ]
+        })
+    }
+
+    /// Every corner around any of `cells`, each listed once.
+    fn around(&self, cells: &[usize]) -> Vec<usize> {
+        let mut corners = cells
+            .iter()
+            .flat_map(|&x| self.ring(x))
+            .map(|&x| x as usize)
+            .collect::<Vec<_>>();
+        corners.sort_unstable();
+        corners.dedup();
+
+        corners
+    }
+
+    /// Centre of a corner, given the displaced centres of the cells.
+    fn position(&self, centres: &[Vec3], corner: usize) -> Vec3 {
+        let [a, b, c] = self.cells[corner].map(|x| centres[x as usize]);
+        (a + b + c) / 3.0
+    }
+
+    /// Colour of a corner, blending the cells meeting there.
+    fn colour(&self, colours: &[Vec3], corner: usize) -> Vec3 {
+        let [a, b, c] = self.cells[corner].map(|x| colours[x as usize]);
+        (a + b + c) / 3.0
+    }
+
+    /// Normal of triangle `k` of `cell`, facing away from the planet and as
+    /// long as twice the triangle's area.
+    fn triangle_normal(&self, sources: &LandSources, cell: usize, k: usize) -> Vec3 {
+        let ring = self.ring(cell);
+        let centre = sources.centres[cell];
+        let [a, b] = [ring[k], ring[(k + 1) % ring.len()]].map(|x| sources.corners[x as usize]);
+
+        let normal = (a - centre).cross(b - centre);
+        if normal.dot(centre) < 0.0 {
+            -normal
+        } else {
+            normal
+        }
+    }
+
+    /// Area weighted normal of the triangles of `cell`.
+    fn cell_normal(&self, sources: &LandSources, cell: usize) -> Vec3 {
+        (0..self.ring(cell).len())
+            .map(|k| self.triangle_normal(sources, cell, k))
+            .sum::<Vec3>()
+            .normalize_or_zero()
+    }
+
+    /// Area weighted normal of the triangles meeting at `corner`. Each of the
+    /// three cells has two triangles on either side of it.
+    fn corner_normal(&self, sources: &LandSources, corner: usize) -> Vec3 {
+        self.cells[corner]
+            .iter()
+            .map(|&cell| {
+                let cell = cell as usize;
+                let len = self.ring(cell).len();
+                let k = self.ring(cell).iter().position(|&x| x == corner as u32).unwrap();
+                self.triangle_normal(sources, cell, (k + len - 1) % len) + self.triangle_normal(sources, cell, k)
+            })
+            .sum::<Vec3>()
+            .normalize_or_zero()
+    }
+
+    /// Every vertex of a flat `cell`, its centre followed by its corners. They
+    /// all take the colour and the normal of the cell, so it is drawn faceted.
+    fn flat_cell(&self, sources: &LandSources, cell: usize, out: &mut [Vertex]) {
+        let normal = self.cell_normal(sources, cell);
+        let colour = to_vertex_colour(sources.colours[cell], 1.0);
+
+        let corners = self.ring(cell).iter().map(|&x| sources.corners[x as usize]);
+        for (out, position) in out.iter_mut().zip(std::iter::once(sources.centres[cell]).chain(corners)) {
+            *out = (position, normal, colour);
+        }
+    }
+
+    /// Vertex `vertex` of a smooth [`IndexedLandGeometry`]. Centres keep the
+    /// colour of their cell and corners blend the cells around them.
+    fn smooth_vertex(&self, sources: &LandSources, vertex: usize) -> Vertex {
+        match vertex.checked_sub(self.cells()) {
+            None => (
+                sources.centres[vertex],
+                self.cell_normal(sources, vertex),
+                to_vertex_colour(sources.colours[vertex], 1.0),
+            ),
+            Some(corner) => (
+                sources.corners[corner],
+                self.corner_normal(sources, corner),
+                to_vertex_colour(self.colour(sources.colours, corner), 1.0),
+            ),
+        }
+    }
+}
+
+/// Position, normal and colour of a vertex.
+type Vertex = (Vec3, Vec3, [u8; 4]);
+
+/// What the vertices of an [`IndexedLandGeometry`] are worked out from.
+struct LandSources<'a> {
+    /// Displaced centre of every cell
+    centres: &'a [Vec3],
+    /// Centre of every corner, see [`CornerTable::position`]
+    corners: &'a [Vec3],
+    /// Shaded colour of every cell
+    colours: &'a [Vec3],
+}
+
+/// The same cells as [`LandGeometry`], fanning out from one vertex in the
+/// centre of every cell to the Voronoi corners around it.
+///
+/// With [`LandShading::Flat`] every cell has its own copy of its corners, all
+/// in the cell's colour and facing along the cell's normal. With
+/// [`LandShading::Smooth`] the three cells meeting at a corner share one
+/// vertex, blending their colours and normals, so each cell keeps its colour
+/// in the middle and fades into its neighbours towards its edges.
+pub struct IndexedLandGeometry {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub colours: Vec<[u8; 4]>,
+    pub indices: Vec<u32>,
+}
+
+impl IndexedLandGeometry {
+    /// Bytes of vertex data, as uploaded to the GPU.
+    pub fn vertex_bytes(&self) -> usize {
+        self.positions.len() * VERTEX_BYTES
+    }
+
+    pub fn index_bytes(&self) -> usize {
+        self.indices.len() * std::mem::size_of::<u32>()
+    }
+
+    fn write(&mut self, vertex: usize, (position, normal, colour): Vertex) {
+        self.positions[vertex] = position;
+        self.normals[vertex] = normal;
+        self.colours[vertex] = colour;
+    }
+}
+
+/// Jitters `colour` if cells are drawn with `shading`.
+fn shade(colour: Vec3, shading: LandShading, jitter: &[f32], cell: usize) -> Vec3 {
+    match shading {
+        LandShading::Flat => colour * jitter[cell],
+        LandShading::Smooth => colour,
+    }
+}
+
+/// Biome colour of every cell as drawn with `shading`.
+fn shaded_colours(world: &World, scale: &ColourScale, colouring: Colouring, shading: LandShading, jitter: &[f32]) -> Vec<Vec3> {
+    map_cells(world.adjacent.len(), |cell| {
+        shade(scale.colour(world, colouring, cell), shading, jitter, cell)
+    })
+}
+
+fn corner_positions(corners: &CornerTable, centres: &[Vec3]) -> Vec<Vec3> {
+    map_cells(corners.len(), |corner| corners.position(centres, corner))
+}
+
+/// Lays out the vertices and triangles of every cell for `shading`.
+fn build_indexed_geometry(corners: &CornerTable, sources: &LandSources, shading: LandShading) -> IndexedLandGeometry {
+    let vertices = match shading {
+        LandShading::Flat => {
+            let mut vertices = vec![(Vec3::ZERO, Vec3::ZERO, [0; 4]); corners.vertices(shading)];
+            fill_cells(
+                &mut vertices,
+                (0..corners.cells()).map(|cell| corners.ring(cell).len() + 1),
+                |cell, out| corners.flat_cell(sources, cell, out),
+            );
+            vertices
+        }
+        LandShading::Smooth => map_cells(corners.vertices(shading), |vertex| corners.smooth_vertex(sources, vertex)),
+    };
+
+    let mut indices = vec![0; corners.rings.len() * 3];
+    fill_cells(
+        &mut indices,
+        (0..corners.cells()).map(|cell| corners.ring(cell).len() * 3),
+        |cell, out| {
+            corners
+                .triangles(cell, shading)
+                .zip(out.chunks_exact_mut(3))
+                .for_each(|(triangle, out)| out.copy_from_slice(&triangle));
+        },
+    );
+
+    IndexedLandGeometry {
+        positions: vertices.iter().map(|x| x.0).collect(),
+        normals: vertices.iter().map(|x| x.1).collect(),
+        colours: vertices.iter().map(|x| x.2).collect(),
+        indices,
+    }
+}
+
+pub fn create_indexed_land_geometry(
+    world: &World,
+    corners: &CornerTable,
+    colouring: Colouring,
+    shading: LandShading,
+) -> IndexedLandGeometry {
+    let scale = ColourScale::new(world, rainfall(world, colouring).as_deref());
+    let colours = shaded_colours(world, &scale, colouring, shading, &jitter(world.adjacent.len()));
+    let centres = scaled_positions(world);
+    let corner_positions = corner_positions(corners, &centres);
+    let sources = LandSources {
+        centres: &centres,
+        corners: &corner_positions,
+        colours: &colours,
+    };
+
+    build_indexed_geometry(corners, &sources, shading)
+}
+
+/// Fraction of the cells an edit may touch before the land mesh is rebuilt
+/// and rescaled from scratch rather than patched.
+pub const FULL_REBUILD_FRACTION: f32 = 0.25;
+
+/// The full resolution land mesh, kept on the CPU between edits so they only
+/// have to redo the cells they touched.
+pub struct LandMeshCache {
+    corners: CornerTable,
+    geometry: IndexedLandGeometry,
+    scale: ColourScale,
+    /// Rainfall as of the last time the world was replaced or the winds
+    /// changed. Other edits keep the rain they had.
+    rainfall: Option<Vec<f32>>,
+    /// Displaced centre of every cell
+    centres: Vec<Vec3>,
+    /// Centre of every corner
+    corner_positions: Vec<Vec3>,
+    /// Shaded colour of every cell
+    colours: Vec<Vec3>,
+    jitter: Vec<f32>,
+    shading: LandShading,
+    temperature: TemperatureOpts,
+    moisture: MoistureOpts,
+}
+
+impl LandMeshCache {
+    pub fn new(world: &World, colouring: Colouring, shading: LandShading) -> Self {
+        let corners = CornerTable::new(world);
+        let rainfall = rainfall(world, colouring);
+        let scale = ColourScale::new(world, rainfall.as_deref());
+        let jitter = jitter(world.adjacent.len());
+        let colours = shaded_colours(world, &scale, colouring, shading, &jitter);
+        let centres = scaled_positions(world);
+        let corner_positions = corner_positions(&corners, &centres);
+
+        let sources = LandSources {
+            centres: &centres,
+            corners: &corner_positions,
+            colours: &colours,
+        };
+        let geometry = build_indexed_geometry(&corners, &sources, shading);
+
+        Self {
+            geometry,
+            corners,
+            scale,
+            rainfall,
+            centres,
+            corner_positions,
+            colours,
+            jitter,
+            shading,
+            temperature: *colouring.temperature,
+            moisture: *colouring.moisture,
+        }
+    }
+
+    pub fn corners(&self) -> &CornerTable {
+        &self.corners
+    }
+
+    pub fn geometry(&self) -> &IndexedLandGeometry {
+        &self.geometry
+    }
+
+    /// Shading the geometry is laid out for.
+    pub fn shading(&self) -> LandShading {
+        self.shading
+    }
+
+    /// Brings the geometry up to date with `world`, given the cells edited
+    /// since the last update. Returns the cells with a vertex that changed,
+    /// or `None` if nothing did.
+    ///
+    /// Edits only redo the cells they touched and their neighbours. Replacing
+    /// the world, changing the climate or the shading, editing a height past
+    /// the colour scale or editing more than [`FULL_REBUILD_FRACTION`] of the
+    /// cells rebuilds everything instead. Only replacing the world, changing
+    /// the winds or an edit asking for `rain` works the rainfall out again,
+    /// everything else keeps the rain. Rain changes the colour of every cell,
+    /// so it only rebuilds everything while the moisture pass is enabled.
+    pub fn update(
+        &mut self,
+        world: &World,
+        colouring: Colouring,
+        shading: LandShading,
+        dirty: Option<Dirty>,
+        rain: bool,
+    ) -> Option<Dirty> {
+        let rain = rain && colouring.moisture.enabled;
+        let restyled = world.adjacent.len() != self.jitter.len()
+            || shading != self.shading
+            || self.temperature != *colouring.temperature
+            || self.moisture != *colouring.moisture;
+
+        let edited = match dirty {
+            None if !restyled && !rain => return None,
+            Some(Dirty::Cells(edited)) if !restyled && !rain && !self.needs_rescale(world, &edited) => edited,
+            dirty => {
+                self.shading = shading;
+                self.rebuild_with(world, colouring, rain || matches!(dirty, Some(Dirty::All)));
+                return Some(Dirty::All);
+            }
         };
 
-        for trio in adj.windows(3) {
-            make(trio[0], trio[1], trio[2]);
+        Some(Dirty::Cells(self.patch(world, colouring, &edited)))
+    }
+
+    fn needs_rescale(&self, world: &World, edited: &[usize]) -> bool {
+        edited.len() as f32 > self.jitter.len() as f32 * FULL_REBUILD_FRACTION
+            || edited.iter().any(|&x| !self.scale.contains(world.heights[x].load()))
+    }
+
+    /// Redoes `edited` and everything around it depending on it, returning
+    /// every cell with a vertex that changed.
+    fn patch(&mut self, world: &World, colouring: Colouring, edited: &[usize]) -> Vec<usize> {
+        // Heights move the edited centres and every corner around them
+        for &cell in edited {
+            self.centres[cell] = world.positions[cell] * world.heights[cell].load();
+            self.scale.wetness_scale[cell] = self.scale.wetness_rank(world.wetness[cell].load());
+        }
+        for corner in self.corners.around(edited) {
+            self.corner_positions[corner] = self.corners.position(&self.centres, corner);
+        }
+
+        // Colours look at the heights of the neighbours, which are also the
+        // cells whose triangles just moved
+        let mut touched = edited
+            .iter()
+            .flat_map(|&x| world.adjacent[x].iter().copied().chain([x]))
+            .collect::<Vec<_>>();
+        touched.sort_unstable();
+        touched.dedup();
+
+        for &cell in &touched {
+            self.colours[cell] = shade(self.scale.colour(world, colouring, cell), self.shading, &self.jitter, cell);
+        }
+
+        let corners = &self.corners;
+        let geometry = &mut self.geometry;
+        let sources = LandSources {
+            centres: &self.centres,
+            corners: &self.corner_positions,
+            colours: &self.colours,
+        };
+
+        match self.shading {
+            LandShading::Flat => {
+                let mut vertices = Vec::new();
+                for &cell in &touched {
+                    vertices.resize(corners.ring(cell).len() + 1, (Vec3::ZERO, Vec3::ZERO, [0; 4]));
+                    corners.flat_cell(&sources, cell, &mut vertices);
+
+                    let start = corners.centre_vertex(cell, LandShading::Flat);
+                    for (idx, &vertex) in vertices.iter().enumerate() {
+                        geometry.write(start + idx, vertex);
+                    }
+                }
+
+                touched
+            }
+            LandShading::Smooth => {
+                // Corners blend the colours and the normals of every cell around them
+                let touched_corners = corners.around(&touched);
+                let vertices = touched
+                    .iter()
+                    .copied()
+                    .chain(touched_corners.iter().map(|&x| corners.cells() + x));
+                for vertex in vertices {
+                    geometry.write(vertex, corners.smooth_vertex(&sources, vertex));
+                }
+
+                // Every cell sharing one of those corners draws it
+                let mut rewritten = touched_corners
+                    .iter()
+                    .flat_map(|&x| corners.cells[x])
+                    .map(|x| x as usize)
+                    .collect::<Vec<_>>();
+                rewritten.sort_unstable();
+                rewritten.dedup();
+
+                rewritten
+            }
         }
+    }
 
-        let len = adj.len();
-        make(adj[len - 2], adj[len - 1], adj[0]);
-        make(adj[len - 1], adj[0], adj[1]);
+    /// Rebuilds the geometry from scratch for when the biomes change, keeping
+    /// the rainfall unless the winds did too.
+    pub fn rebuild(&mut self, world: &World, colouring: Colouring) {
+        self.rebuild_with(world, colouring, false);
     }
 
-    MeshBuilder::new(inner_points, Handedness::Left)
-        .with_vertex_color_0(inner_colours)
-        .build()
-        .unwrap()
+    /// Rebuilds the geometry from scratch, working the rainfall out again if
+    /// the world was `replaced`, the land reshaped or the rain doesn't hold
+    /// anymore.
+    fn rebuild_with(&mut self, world: &World, colouring: Colouring, replaced: bool) {
+        let resized = world.adjacent.len() != self.jitter.len();
+        if resized {
+            self.corners = CornerTable::new(world);
+            self.jitter = jitter(world.adjacent.len());
+        }
+
+        let previous = Colouring {
+            temperature: &self.temperature,
+            moisture: &self.moisture,
+            ..colouring
+        };
+        if resized || replaced || !same_rain(previous, colouring) {
+            self.rainfall = rainfall(world, colouring);
+        }
+
+        self.scale = ColourScale::new(world, self.rainfall.as_deref());
+        self.colours = shaded_colours(world, &self.scale, colouring, self.shading, &self.jitter);
+        self.centres = scaled_positions(world);
+        self.corner_positions = corner_positions(&self.corners, &self.centres);
+
+        let sources = LandSources {
+            centres: &self.centres,
+            corners: &self.corner_positions,
+            colours: &self.colours,
+        };
+        self.geometry = build_indexed_geometry(&self.corners, &sources, self.shading);
+        self.temperature = *colouring.temperature;
+        self.moisture = *colouring.moisture;
+    }
 }
 
 fn get_sorted_idx(items: impl Iterator<Item = f32>, filter: impl Fn(f32) -> bool, len: usize) -> Vec<f32> {
     let mut ord = items.enumerate().filter(|&(_, x)| filter(x)).collect::<Vec<_>>();
-    ord.sort_by(|x, y| x.1.total_cmp(&y.1));
+    sort_cells(&mut ord, |x, y| x.1.total_cmp(&y.1));
     let mut scale = vec![0.0; len];
     let len = ord.len();
     ord
@@ -101,3 +864,133 @@ fn get_sorted_idx(items: impl Iterator<Item = f32>, filter: impl Fn(f32) -> bool
 
     scale
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::widgets::worldgen::WorldGenWidget;
+
+    fn small_world() -> World {
+        let mut gen = WorldGenWidget::default();
+        gen.opts.subdivisions = 10;
+        gen.get_state()
+    }
+
+    struct Climate {
+        biomes: BiomeTable,
+        temperature: TemperatureOpts,
+        moisture: MoistureOpts,
+    }
+
+    impl Climate {
+        fn new(rainfall: bool) -> Self {
+            Self {
+                biomes: BiomeTable::default(),
+                temperature: TemperatureOpts::default(),
+                moisture: MoistureOpts {
+                    enabled: rainfall,
+                    ..Default::default()
+                },
+            }
+        }
+
+        fn colouring(&self) -> Colouring<'_> {
+            Colouring {
+                biomes: &self.biomes,
+                temperature: &self.temperature,
+                moisture: &self.moisture,
+            }
+        }
+    }
+
+    #[test]
+    fn patching_an_edit_matches_a_rebuild() {
+        let world = small_world();
+        // Rainfall follows the heights, and is only worked out on full rebuilds
+        let climate = Climate::new(false);
+        let colouring = climate.colouring();
+        let mut caches = [LandShading::Flat, LandShading::Smooth].map(|x| LandMeshCache::new(&world, colouring, x));
+
+        // Move a cell towards the middle of the range, so the scale stays put
+        let (min, max) = height_range(&world);
+        let cell = (0..world.heights.len())
+            .find(|&x| (min..max).contains(&world.heights[x].load()) && world.heights[x].load() != min)
+            .unwrap();
+        world.heights[cell].store((world.heights[cell].load() + (min + max) / 2.0) / 2.0);
+
+        for cache in caches.iter_mut() {
+            let shading = cache.shading();
+            // Asking for rain without the moisture pass still only patches
+            match cache.update(&world, colouring, shading, Some(Dirty::Cells(vec![cell])), true) {
+                Some(Dirty::Cells(cells)) => assert!(cells.contains(&cell) && cells.len() < 64),
+                other => panic!("expected a patch, got {:?}", other),
+            }
+
+            let fresh = LandMeshCache::new(&world, colouring, shading);
+            assert_eq!(cache.geometry.positions, fresh.geometry.positions, "{:?}", shading);
+            assert_eq!(cache.geometry.normals, fresh.geometry.normals, "{:?}", shading);
+            assert_eq!(cache.geometry.colours, fresh.geometry.colours, "{:?}", shading);
+            assert_eq!(cache.geometry.indices, fresh.geometry.indices, "{:?}", shading);
+        }
+    }
+
+    #[test]
+    fn flat_cells_are_one_colour_and_face_one_way() {
+        let world = small_world();
+        let climate = Climate::new(false);
+        let corners = CornerTable::new(&world);
+        let flat = create_indexed_land_geometry(&world, &corners, climate.colouring(), LandShading::Flat);
+        let smooth = create_indexed_land_geometry(&world, &corners, climate.colouring(), LandShading::Smooth);
+
+        let fan = |geometry: &IndexedLandGeometry, cell: usize, shading: LandShading| {
+            corners
+                .triangles(cell, shading)
+                .flatten()
+                .map(|x| (geometry.colours[x as usize], geometry.normals[x as usize]))
+                .collect::<Vec<_>>()
+        };
+
+        let mut blended = 0;
+        for cell in 0..world.adjacent.len() {
+            let vertices = fan(&flat, cell, LandShading::Flat);
+            assert!(vertices.iter().all(|&x| x == vertices[0]), "cell {} isn't flat", cell);
+
+            // Smooth cells fade into their neighbours instead
+            let vertices = fan(&smooth, cell, LandShading::Smooth);
+            blended += vertices.iter().any(|&x| x.0 != vertices[0].0) as usize;
+        }
+        assert!(blended > 0);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn serial_and_parallel_builds_match() {
+        let world = small_world();
+        let climate = Climate::new(true);
+        let colouring = climate.colouring();
+
+        let build = |serial: bool| {
+            SERIAL.with(|x| x.set(serial));
+            let corners = CornerTable::new(&world);
+            let built = (
+                create_land_geometry(&world, colouring),
+                [LandShading::Flat, LandShading::Smooth].map(|x| create_indexed_land_geometry(&world, &corners, colouring, x)),
+            );
+            SERIAL.with(|x| x.set(false));
+
+            built
+        };
+
+        let (serial, serial_indexed) = build(true);
+        let (parallel, parallel_indexed) = build(false);
+
+        assert_eq!(serial.positions, parallel.positions);
+        assert_eq!(serial.colours, parallel.colours);
+        for (serial, parallel) in serial_indexed.iter().zip(parallel_indexed.iter()) {
+            assert_eq!(serial.positions, parallel.positions);
+            assert_eq!(serial.normals, parallel.normals);
+            assert_eq!(serial.colours, parallel.colours);
+            assert_eq!(serial.indices, parallel.indices);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use rend3::Renderer;
+use sphere_terrain::World;
+use crate::geometry::land::{create_indexed_land_geometry, nearest_cell, Colouring, CornerTable, LandMeshCache};
+use crate::geometry::patches::{LandPatch, PatchLayout};
+use crate::util::dirty::{Dirty, DirtyCells};
+use crate::util::widgets::shading::LandShading;
+
+/// Most levels kept, including the full resolution one.
+pub const LOD_LEVELS: usize = 4;
+
+/// Coarsest subdivisions a level may be resampled to.
+pub const MIN_LOD_SUBDIVISIONS: usize = 8;
+
+/// Orbit distance past which the first coarse level is used. Every level after
+/// that kicks in at twice the distance of the previous one.
+pub const LOD_BASE_DISTANCE: f32 = 12.0;
+
+/// Fraction of a threshold the distance has to overshoot it by before the
+/// level changes, so zooming around a threshold doesn't pop back and forth.
+pub const LOD_HYSTERESIS: f32 = 0.15;
+
+/// Subdivisions of the hexasphere `world` was built from.
+pub fn subdivisions(world: &World) -> usize {
+    // A hexasphere with `s` subdivisions has `10 * (s + 1)^2 + 2` cells
+    let cells = world.positions.len().saturating_sub(2) / 10;
+    ((cells as f64).sqrt().round() as usize).saturating_sub(1)
+}
+
+/// Copies `world` onto a coarser hexasphere, each coarse cell taking the values
+/// of the nearest fine one.
+pub fn resample(world: &World, subdivisions: usize) -> World {
+    let coarse = World::new(subdivisions, world.settings);
+
+    let mut last = 0;
+    for (idx, position) in coarse.positions.iter().enumerate() {
+        last = nearest_cell(world, position.normalize(), last);
+
+        coarse.heights[idx].store(world.heights[last].load());
+        coarse.hardness[idx].store(world.hardness[last].load());
+        coarse.wetness[idx].store(world.wetness[last].load());
+    }
+
+    coarse
+}
+
+struct LodLevel {
+    subdivisions: usize,
+    /// Cleared whenever the colouring or shading changes, and rebuilt the next
+    /// time the level is shown. Coarse levels are also cleared by edits, which
+    /// the full resolution level patches in place instead.
+    patches: Option<Vec<LandPatch>>,
+}
+
+/// The land at several resolutions, picked by how far away the orbit camera is.
+///
+/// Only the level being shown is kept up to date, the rest are rebuilt when
+/// the camera next gets to them.
+pub struct LandLod {
+    cache: LandMeshCache,
+    /// How the full resolution level is split into patches
+    layout: Option<PatchLayout>,
+    /// Edits the full resolution level hasn't caught up with yet
+    pending: DirtyCells,
+    levels: Vec<LodLevel>,
+    current: usize,
+}
+
+impl LandLod {
+    pub fn new(world: &World, colouring: Colouring, shading: LandShading) -> Self {
+        let mut lod = Self {
+            cache: LandMeshCache::new(world, colouring, shading),
+            layout: None,
+            pending: DirtyCells::default(),
+            levels: Vec::new(),
+            current: 0,
+        };
+        lod.make_levels(world);
+
+        lod
+    }
+
+    fn make_levels(&mut self, world: &World) {
+        let full = subdivisions(world);
+
+        self.levels = (0..LOD_LEVELS)
+            .map(|level| full >> level)
+            .take_while(|&subdivisions| subdivisions == full || subdivisions >= MIN_LOD_SUBDIVISIONS)
+            .map(|subdivisions| LodLevel { subdivisions, patches: None })
+            .collect();
+        self.current = self.current.min(self.levels.len() - 1);
+        self.layout = None;
+    }
+
+    /// Distance past which `level` is shown, level 0 always being shown up close.
+    fn threshold(level: usize) -> f32 {
+        match level {
+            0 => 0.0,
+            _ => LOD_BASE_DISTANCE * (1 << (level - 1)) as f32,
+        }
+    }
+
+    /// Marks every level as out of date, to be called whenever the colouring
+    /// or the shading changes.
+    pub fn invalidate(&mut self, world: &World) {
+        if self.levels[0].subdivisions != subdivisions(world) {
+            self.make_levels(world);
+        }
+
+        self.levels.iter_mut().for_each(|x| x.patches = None);
+    }
+
+    /// Queues cells edited in `world` for the full resolution level to patch,
+    /// and marks the coarse levels as out of date. With `rain` set the edit
+    /// changed where it rains too, see [`DirtyCells::mark_rain`].
+    pub fn edit(&mut self, world: &World, dirty: Dirty, rain: bool) {
+        if self.levels[0].subdivisions != subdivisions(world) {
+            self.make_levels(world);
+        }
+
+        self.pending.extend(dirty);
+        if rain {
+            self.pending.mark_rain();
+        }
+        self.levels.iter_mut().skip(1).for_each(|x| x.patches = None);
+    }
+
+    /// Like `invalidate`, but also recolours every cell, for when the biomes
+    /// change without the world doing so.
+    pub fn recolour(&mut self, world: &World, colouring: Colouring) {
+        self.cache.rebuild(world, colouring);
+        self.invalidate(world);
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn current_subdivisions(&self) -> usize {
+        self.levels[self.current].subdivisions
+    }
+
+    /// Picks the level for a camera `distance` away, returning true if it
+    /// changed.
+    pub fn select(&mut self, distance: f32) -> bool {
+        let previous = self.current;
+
+        while self.current + 1 < self.levels.len()
+            && distance > Self::threshold(self.current + 1) * (1.0 + LOD_HYSTERESIS)
+        {
+            self.current += 1;
+        }
+
+        while self.current > 0 && distance < Self::threshold(self.current) * (1.0 - LOD_HYSTERESIS) {
+            self.current -= 1;
+        }
+
+        self.current != previous
+    }
+
+    /// Patches of the current level, building them if they are out of date.
+    /// At full resolution only the patches holding edited cells are rebuilt.
+    pub fn patches(&mut self, renderer: &Renderer, world: &World, colouring: Colouring, shading: LandShading) -> &[LandPatch] {
+        let level = &mut self.levels[self.current];
+
+        if self.current == 0 {
+            let rain = self.pending.take_rain();
+            let rewritten = self.cache.update(world, colouring, shading, self.pending.take(), rain);
+            // A rebuild may have changed the cells or the shading the layout was made for
+            if matches!(rewritten, Some(Dirty::All)) {
+                self.layout = None;
+            }
+
+            let cache = &self.cache;
+            let layout = self
+                .layout
+                .get_or_insert_with(|| PatchLayout::new(cache.corners(), &world.positions, cache.shading()));
+
+            match (&mut level.patches, rewritten) {
+                (Some(patches), Some(Dirty::Cells(cells))) => {
+                    layout.rebuild(renderer, cache.geometry(), patches, &cells);
+                }
+                (Some(_), None) => {}
+                (patches, _) => *patches = Some(layout.build(renderer, cache.geometry())),
+            }
+        } else if level.patches.is_none() {
+            level.patches = Some(Self::build_coarse(renderer, level.subdivisions, world, colouring, shading));
+        }
+
+        level.patches.as_deref().unwrap()
+    }
+
+    fn build_coarse(renderer: &Renderer, subdivisions: usize, world: &World, colouring: Colouring, shading: LandShading) -> Vec<LandPatch> {
+        let coarse = resample(world, subdivisions);
+        let corners = CornerTable::new(&coarse);
+        let geometry = create_indexed_land_geometry(&coarse, &corners, colouring, shading);
+
+        PatchLayout::new(&corners, &coarse.positions, shading).build(renderer, &geometry)
+    }
+}
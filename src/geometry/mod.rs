@@ -1 +1,6 @@
+pub mod clouds;
 pub mod land;
+pub mod lod;
+pub mod ocean;
+pub mod patches;
+pub mod rivers;
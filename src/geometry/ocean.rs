@@ -0,0 +1,150 @@
+use glam::{Mat4, Vec3};
+use hexasphere::shapes::IcoSphere;
+use rend3::types::{MaterialHandle, MeshHandle, Object, ObjectHandle, ObjectMeshKind};
+use rend3::Renderer;
+use rend3_types::{Handedness, Mesh, MeshBuilder};
+use crate::util::widgets::material::PbrMaterialWidget;
+use crate::util::widgets::waves::WaterWavesWidget;
+
+/// Height of the water surface, the same threshold `create_land_mesh` uses to
+/// decide whether a cell borders the sea.
+pub const SEA_LEVEL: f32 = 1.0;
+
+/// Subdivisions of the water sphere, independent of the land resolution.
+pub const OCEAN_SUBDIVISIONS: usize = 60;
+
+/// Unit sphere the water surface is displaced from.
+pub struct OceanSphere {
+    pub points: Vec<Vec3>,
+    pub indices: Vec<u32>,
+}
+
+impl OceanSphere {
+    pub fn new(subdivisions: usize) -> Self {
+        let sphere = IcoSphere::new(subdivisions, |_| ());
+
+        Self {
+            points: sphere.raw_points().iter().map(|&x| Vec3::from(x)).collect(),
+            indices: sphere.get_all_indices(),
+        }
+    }
+
+    pub fn create_mesh(&self) -> Mesh {
+        let positions = self
+            .points
+            .iter()
+            .map(|&x| x * SEA_LEVEL)
+            .collect::<Vec<_>>();
+
+        MeshBuilder::new(positions, Handedness::Left)
+            .with_vertex_normals(self.points.clone())
+            .with_indices(self.indices.clone())
+            .build()
+            .unwrap()
+    }
+
+    /// Builds the water surface with `waves` applied at `time` seconds. Normals
+    /// are left to the mesh builder to recompute from the displaced triangles.
+    pub fn create_waves_mesh(&self, waves: &WaterWavesWidget, time: f32) -> Mesh {
+        let positions = self
+            .points
+            .iter()
+            .map(|&x| waves.displace(x, SEA_LEVEL, time))
+            .collect::<Vec<_>>();
+
+        MeshBuilder::new(positions, Handedness::Left)
+            .with_indices(self.indices.clone())
+            .build()
+            .unwrap()
+    }
+}
+
+pub struct Ocean {
+    pub sphere: OceanSphere,
+    pub mesh: MeshHandle,
+    pub material: MaterialHandle,
+    pub object: Object,
+    pub handle: ObjectHandle,
+    /// Time the current waves mesh shows, `None` if it is flat or outdated
+    shown: Option<f32>,
+}
+
+impl Ocean {
+    pub fn new(renderer: &Renderer, material: &PbrMaterialWidget, transform: Mat4) -> Self {
+        let sphere = OceanSphere::new(OCEAN_SUBDIVISIONS);
+        let mesh = renderer.add_mesh(sphere.create_mesh());
+        let material = renderer.add_material(material.get_state(false));
+
+        let object = Object {
+            mesh_kind: ObjectMeshKind::Static(mesh.clone()),
+            material: material.clone(),
+            transform,
+        };
+        let handle = renderer.add_object(object.clone());
+
+        Self {
+            sphere,
+            mesh,
+            material,
+            object,
+            handle,
+            shown: None,
+        }
+    }
+
+    pub fn set_mesh(&mut self, renderer: &Renderer, mesh: Mesh) {
+        self.mesh = renderer.add_mesh(mesh);
+        self.object.mesh_kind = ObjectMeshKind::Static(self.mesh.clone());
+        self.handle = renderer.add_object(self.object.clone());
+    }
+
+    /// Moves the water surface to where `waves` put it at `time` seconds.
+    ///
+    /// Every vertex is displaced again and uploaded as a new mesh each time, so
+    /// this does nothing while time stands still and otherwise rebuilds at
+    /// most `waves.update_rate` times a second.
+    pub fn animate(&mut self, renderer: &Renderer, waves: &WaterWavesWidget, time: f32) {
+        if !waves.enabled {
+            return;
+        }
+
+        // Time only runs backwards when a replay starts over, which has to show
+        if let Some(shown) = self.shown {
+            if (0.0..1.0 / waves.update_rate.max(1.0)).contains(&(time - shown)) {
+                return;
+            }
+        }
+
+        let mesh = self.sphere.create_waves_mesh(waves, time);
+        self.set_mesh(renderer, mesh);
+        self.shown = Some(time);
+    }
+
+    /// Shows the water material and wave editors, pushing any change straight
+    /// to the renderer.
+    pub fn render_on(
+        &mut self,
+        ui: &mut egui::Ui,
+        renderer: &Renderer,
+        material: &mut PbrMaterialWidget,
+        waves: &mut WaterWavesWidget,
+    ) {
+        ui.collapsing("Material", |ui| {
+            if let Some(state) = material.render_on(ui, false) {
+                renderer.update_material(&self.material, state);
+            }
+        });
+
+        ui.collapsing("Waves", |ui| {
+            if waves.render_on(ui) {
+                // Shows the edit on the next frame rather than after the interval
+                self.shown = None;
+
+                if !waves.enabled {
+                    let mesh = self.sphere.create_mesh();
+                    self.set_mesh(renderer, mesh);
+                }
+            }
+        });
+    }
+}
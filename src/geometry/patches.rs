@@ -0,0 +1,229 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use glam::{Mat4, Vec3};
+use rend3::types::{MaterialHandle, MeshHandle, Object, ObjectHandle, ObjectMeshKind};
+use rend3::Renderer;
+use rend3_types::{Handedness, MeshBuilder};
+use crate::geometry::land::{CornerTable, IndexedLandGeometry};
+use crate::util::widgets::shading::LandShading;
+use crate::util::camera::frustum::Frustum;
+
+/// Directions to the centres of the 20 faces of an icosahedron.
+///
+/// Every direction is closest to the centre of the face it passes through, so
+/// picking the nearest centre splits the sphere along the base triangles.
+fn face_centres() -> Vec<Vec3> {
+    let phi = (1.0 + 5.0f32.sqrt()) / 2.0;
+
+    let mut corners = Vec::with_capacity(12);
+    for a in [-1.0, 1.0] {
+        for b in [-phi, phi] {
+            corners.push(Vec3::new(0.0, a, b));
+            corners.push(Vec3::new(a, b, 0.0));
+            corners.push(Vec3::new(b, 0.0, a));
+        }
+    }
+
+    // All edges have length 2
+    let is_edge = |a: usize, b: usize| (corners[a].distance(corners[b]) - 2.0).abs() < 1e-3;
+
+    let mut centres = Vec::with_capacity(20);
+    for a in 0..corners.len() {
+        for b in a + 1..corners.len() {
+            for c in b + 1..corners.len() {
+                if is_edge(a, b) && is_edge(b, c) && is_edge(a, c) {
+                    centres.push((corners[a] + corners[b] + corners[c]).normalize());
+                }
+            }
+        }
+    }
+
+    centres
+}
+
+fn nearest_face(centres: &[Vec3], direction: Vec3) -> usize {
+    centres
+        .iter()
+        .enumerate()
+        .max_by(|x, y| x.1.dot(direction).total_cmp(&y.1.dot(direction)))
+        .map(|x| x.0)
+        .unwrap()
+}
+
+/// Hands every patch built a new id, so objects are only re-added for the
+/// patches that were rebuilt.
+static NEXT_PATCH_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Piece of the land mesh, with a sphere bounding it in planet space.
+pub struct LandPatch {
+    pub id: u64,
+    pub mesh: MeshHandle,
+    pub centre: Vec3,
+    pub radius: f32,
+}
+
+/// Vertices of the whole geometry a patch uses, and its triangles indexing
+/// into them.
+#[derive(Default)]
+struct PatchPart {
+    vertices: Vec<u32>,
+    indices: Vec<u32>,
+}
+
+impl PatchPart {
+    fn build(&self, renderer: &Renderer, geometry: &IndexedLandGeometry) -> LandPatch {
+        let positions = self.vertices.iter().map(|&x| geometry.positions[x as usize]).collect::<Vec<_>>();
+        let normals = self.vertices.iter().map(|&x| geometry.normals[x as usize]).collect();
+        let colours = self.vertices.iter().map(|&x| geometry.colours[x as usize]).collect();
+
+        let (min, max) = positions
+            .iter()
+            .fold((Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)), |(min, max), &x| (min.min(x), max.max(x)));
+        let centre = (min + max) / 2.0;
+        let radius = positions
+            .iter()
+            .map(|&x| x.distance(centre))
+            .fold(0.0, f32::max);
+
+        let mesh = MeshBuilder::new(positions, Handedness::Left)
+            .with_vertex_normals(normals)
+            .with_vertex_color_0(colours)
+            .with_indices(self.indices.clone())
+            .build()
+            .unwrap();
+
+        LandPatch {
+            id: NEXT_PATCH_ID.fetch_add(1, Ordering::Relaxed),
+            mesh: renderer.add_mesh(mesh),
+            centre,
+            radius,
+        }
+    }
+}
+
+/// How the cells of an [`IndexedLandGeometry`] are split into patches, one per
+/// icosahedron base face. The vertices differ by shading, so the layout is
+/// only good for geometry drawn with the shading it was made for.
+///
+/// Every cell goes in the face its centre lies in, taking the corners around
+/// it along. Vertices on the borders are repeated in each patch, keeping the
+/// normals of the whole mesh, and an edit only has to rebuild the patches
+/// holding the cells it changed.
+pub struct PatchLayout {
+    /// Patch every cell is in
+    patch_of: Vec<u32>,
+    parts: Vec<PatchPart>,
+}
+
+impl PatchLayout {
+    pub fn new(corners: &CornerTable, centres: &[Vec3], shading: LandShading) -> Self {
+        let faces = face_centres();
+
+        let mut members = vec![Vec::new(); faces.len()];
+        for (cell, &centre) in centres.iter().enumerate() {
+            members[nearest_face(&faces, centre.normalize_or_zero())].push(cell);
+        }
+
+        // Which patch last used a vertex, and where it put it
+        let mut remap = vec![(u32::MAX, 0u32); corners.vertices(shading)];
+        let mut patch_of = vec![0; centres.len()];
+        let mut parts = Vec::with_capacity(faces.len());
+
+        for cells in members.into_iter().filter(|x| !x.is_empty()) {
+            let patch = parts.len() as u32;
+            let mut part = PatchPart::default();
+
+            for cell in cells {
+                patch_of[cell] = patch;
+
+                for vertex in corners.triangles(cell, shading).flatten() {
+                    let vertex = vertex as usize;
+                    if remap[vertex].0 != patch {
+                        remap[vertex] = (patch, part.vertices.len() as u32);
+                        part.vertices.push(vertex as u32);
+                    }
+                    part.indices.push(remap[vertex].1);
+                }
+            }
+
+            parts.push(part);
+        }
+
+        Self { patch_of, parts }
+    }
+
+    pub fn build(&self, renderer: &Renderer, geometry: &IndexedLandGeometry) -> Vec<LandPatch> {
+        self.parts.iter().map(|part| part.build(renderer, geometry)).collect()
+    }
+
+    /// Rebuilds the patches holding any of `cells`, returning how many it
+    /// rebuilt.
+    pub fn rebuild(&self, renderer: &Renderer, geometry: &IndexedLandGeometry, patches: &mut [LandPatch], cells: &[usize]) -> usize {
+        let mut stale = vec![false; self.parts.len()];
+        cells.iter().for_each(|&x| stale[self.patch_of[x] as usize] = true);
+
+        let mut rebuilt = 0;
+        for ((patch, part), stale) in patches.iter_mut().zip(&self.parts).zip(stale) {
+            if stale {
+                *patch = part.build(renderer, geometry);
+                rebuilt += 1;
+            }
+        }
+
+        rebuilt
+    }
+}
+
+/// Objects for the land patches that are in view.
+pub struct LandObjects {
+    material: MaterialHandle,
+    transform: Mat4,
+    /// Object of every patch in view, and the id of the patch it was made for
+    objects: Vec<Option<(u64, ObjectHandle)>>,
+    pub drawn: usize,
+    pub culled: usize,
+}
+
+impl LandObjects {
+    pub fn new(material: MaterialHandle, transform: Mat4) -> Self {
+        Self {
+            material,
+            transform,
+            objects: Vec::new(),
+            drawn: 0,
+            culled: 0,
+        }
+    }
+
+    /// Adds objects for the patches `frustum` can see and drops the rest.
+    /// Patches rebuilt since the last update get their object re-added.
+    pub fn update(&mut self, renderer: &Renderer, patches: &[LandPatch], frustum: &Frustum) {
+        if self.objects.len() != patches.len() {
+            self.objects = patches.iter().map(|_| None).collect();
+        }
+
+        let (scale, _, _) = self.transform.to_scale_rotation_translation();
+        self.drawn = 0;
+        self.culled = 0;
+
+        for (patch, object) in patches.iter().zip(self.objects.iter_mut()) {
+            let centre = self.transform.transform_point3(patch.centre);
+            if !frustum.intersects_sphere(centre, patch.radius * scale.max_element()) {
+                *object = None;
+                self.culled += 1;
+                continue;
+            }
+
+            self.drawn += 1;
+            if !matches!(object, Some((id, _)) if *id == patch.id) {
+                *object = Some((
+                    patch.id,
+                    renderer.add_object(Object {
+                        mesh_kind: ObjectMeshKind::Static(patch.mesh.clone()),
+                        material: self.material.clone(),
+                        transform: self.transform,
+                    }),
+                ));
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use glam::{Mat4, Vec3};
+use rend3::types::{MaterialHandle, MeshHandle, Object, ObjectHandle, ObjectMeshKind};
+use rend3::Renderer;
+use rend3_types::{Handedness, Mesh, MeshBuilder};
+use sphere_terrain::World;
+use crate::geometry::ocean::SEA_LEVEL;
+use crate::rivers::RiverNetwork;
+use crate::util::widgets::material::PbrMaterialWidget;
+use crate::util::widgets::rivers::RiverWidget;
+
+/// Builds a ribbon along every river, lying on the filled heights so rivers
+/// run across lakes instead of dipping under them.
+///
+/// Ribbons are drawn from both sides, with normals pointing away from the planet.
+/// Returns `None` if there are no rivers.
+pub fn create_river_mesh(world: &World, network: &RiverNetwork, options: &RiverWidget) -> Option<Mesh> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut indices = Vec::new();
+
+    for river in &network.rivers {
+        let points = river
+            .iter()
+            .map(|&x| {
+                let direction = world.positions[x].normalize();
+                direction * (network.filled[x].max(SEA_LEVEL) + options.lift)
+            })
+            .collect::<Vec<_>>();
+
+        let start = positions.len() as u32;
+
+        for (idx, &point) in points.iter().enumerate() {
+            let tangent = points[(idx + 1).min(points.len() - 1)] - points[idx.saturating_sub(1)];
+            let normal = point.normalize();
+            let width = options.width / 2.0 * (network.accumulation[river[idx]] / options.threshold).sqrt();
+            let side = tangent.cross(normal).normalize_or_zero() * width;
+
+            positions.extend_from_slice(&[point - side, point + side]);
+            normals.extend_from_slice(&[normal, normal]);
+        }
+
+        for idx in 0..points.len() as u32 - 1 {
+            let [left, right, next_left, next_right] = [0, 1, 2, 3].map(|x| start + idx * 2 + x);
+
+            indices.extend_from_slice(&[left, right, next_left, right, next_right, next_left]);
+            indices.extend_from_slice(&[left, next_left, right, right, next_left, next_right]);
+        }
+    }
+
+    if indices.is_empty() {
+        return None;
+    }
+
+    Some(
+        MeshBuilder::new(positions, Handedness::Left)
+            .with_vertex_normals(normals)
+            .with_indices(indices)
+            .build()
+            .unwrap()
+    )
+}
+
+pub struct Rivers {
+    pub network: Option<RiverNetwork>,
+    pub material: MaterialHandle,
+    pub mesh: Option<MeshHandle>,
+    pub handle: Option<ObjectHandle>,
+    pub transform: Mat4,
+}
+
+impl Rivers {
+    pub fn new(renderer: &Renderer, world: &World, options: &RiverWidget, material: &PbrMaterialWidget, transform: Mat4) -> Self {
+        let mut rivers = Self {
+            network: None,
+            material: renderer.add_material(material.get_state(false)),
+            mesh: None,
+            handle: None,
+            transform,
+        };
+
+        rivers.rebuild(renderer, world, options);
+
+        rivers
+    }
+
+    /// Drains the world again and rebuilds the ribbons, or removes them if
+    /// rivers are turned off.
+    pub fn rebuild(&mut self, renderer: &Renderer, world: &World, options: &RiverWidget) {
+        self.network = options
+            .enabled
+            .then(|| RiverNetwork::new(world, options.threshold));
+        self.mesh = self
+            .network
+            .as_ref()
+            .and_then(|network| create_river_mesh(world, network, options))
+            .map(|mesh| renderer.add_mesh(mesh));
+        self.handle = self.mesh.as_ref().map(|mesh| {
+            renderer.add_object(Object {
+                mesh_kind: ObjectMeshKind::Static(mesh.clone()),
+                material: self.material.clone(),
+                transform: self.transform,
+            })
+        });
+    }
+
+    pub fn render_on(
+        &mut self,
+        ui: &mut egui::Ui,
+        renderer: &Renderer,
+        world: &World,
+        material: &mut PbrMaterialWidget,
+        options: &mut RiverWidget,
+    ) {
+        ui.collapsing("Material", |ui| {
+            if let Some(state) = material.render_on(ui, false) {
+                renderer.update_material(&self.material, state);
+            }
+        });
+
+        if options.render_on(ui) {
+            self.rebuild(renderer, world, options);
+        }
+
+        if let Some(network) = &self.network {
+            ui.label(format!("{} rivers", network.rivers.len()));
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use crate::export::{geojson, glb, maps, obj, ply};
+use crate::geometry::land::create_land_geometry;
+use crate::rivers::RiverNetwork;
+use crate::snapshot::WorldSnapshot;
+use crate::util::widgets;
+
+/// Options for `--headless`, falling back to the values stored in the settings file.
+pub struct HeadlessOpts {
+    pub settings: String,
+    pub out: String,
+    pub from: Option<String>,
+    pub drops: Option<usize>,
+    pub blur: Option<usize>,
+    pub rounds: usize,
+    pub glb: Option<String>,
+    pub obj: Option<String>,
+    pub ply: Option<String>,
+    pub maps: Option<String>,
+    pub map_width: usize,
+    pub cube_size: Option<usize>,
+    pub rivers: Option<String>,
+}
+
+const USAGE: &str = "\
+usage: sailer --headless [options]
+
+options:
+    --settings <path>   widget settings to generate from (default: widget_settings)
+    --from <path>       continue from a world snapshot instead of generating noise
+    --out <path>        where to write the world snapshot (default: world_snapshot)
+    --drops <n>         drops per round (default: num_iters from the settings)
+    --blur <n>          blur passes per round (default: blur_iters from the settings)
+    --rounds <n>        number of times to run the drops (default: 1)
+    --glb <path>        also export the land mesh as binary glTF
+    --obj <path>        also export the land mesh as OBJ, with an MTL beside it
+    --ply <path>        also export the land mesh as binary PLY
+    --maps <prefix>     also write height, wetness and biome maps to <prefix>_*.png
+    --map-width <n>     width of the equirectangular maps (default: 2048)
+    --cube-size <n>     also write cube map faces of this size
+    --rivers <path>     also write the river network as GeoJSON";
+
+impl HeadlessOpts {
+    /// Parses the arguments following `--headless`.
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut opts = Self {
+            settings: "widget_settings".to_string(),
+            out: "world_snapshot".to_string(),
+            from: None,
+            drops: None,
+            blur: None,
+            rounds: 1,
+            glb: None,
+            obj: None,
+            ply: None,
+            maps: None,
+            map_width: 2048,
+            cube_size: None,
+            rivers: None,
+        };
+
+        fn number(flag: &str, value: Option<String>) -> Result<usize, String> {
+            value
+                .ok_or_else(|| format!("missing value for {}", flag))?
+                .parse()
+                .map_err(|e| format!("invalid value for {}: {}", flag, e))
+        }
+
+        while let Some(arg) = args.next() {
+            match &*arg {
+                "--settings" => opts.settings = args.next().ok_or("missing value for --settings")?,
+                "--out" => opts.out = args.next().ok_or("missing value for --out")?,
+                "--from" => opts.from = Some(args.next().ok_or("missing value for --from")?),
+                "--drops" => opts.drops = Some(number(&arg, args.next())?),
+                "--blur" => opts.blur = Some(number(&arg, args.next())?),
+                "--rounds" => opts.rounds = number(&arg, args.next())?,
+                "--glb" => opts.glb = Some(args.next().ok_or("missing value for --glb")?),
+                "--obj" => opts.obj = Some(args.next().ok_or("missing value for --obj")?),
+                "--ply" => opts.ply = Some(args.next().ok_or("missing value for --ply")?),
+                "--maps" => opts.maps = Some(args.next().ok_or("missing value for --maps")?),
+                "--map-width" => opts.map_width = number(&arg, args.next())?,
+                "--cube-size" => opts.cube_size = Some(number(&arg, args.next())?),
+                "--rivers" => opts.rivers = Some(args.next().ok_or("missing value for --rivers")?),
+                "--help" | "-h" => return Err(USAGE.to_string()),
+                _ => return Err(format!("unknown argument {}\n\n{}", arg, USAGE)),
+            }
+        }
+
+        Ok(opts)
+    }
+}
+
+pub fn run(opts: HeadlessOpts) -> Result<(), String> {
+    let mut widgets = widgets::load_from_path(&opts.settings)
+        .ok_or_else(|| format!("could not read settings from {}", opts.settings))?;
+    // Never write back over the settings we were handed.
+    widgets.should_save = false;
+
+    let mut gen = widgets.land_options;
+    let drops = opts.drops.unwrap_or(gen.num_iters);
+    let blur = opts.blur.unwrap_or(gen.blur_iters);
+
+    let mut world = match &opts.from {
+        Some(path) => {
+            println!("Restoring world from {}", path);
+            let snapshot = WorldSnapshot::load(path)
+                .map_err(|e| format!("could not read {}: {}", path, e))?;
+            gen.restore(&snapshot)?
+        }
+        None => {
+            println!("Generating world with {} subdivisions", gen.opts.subdivisions);
+            gen.get_state()
+        }
+    };
+    println!("worldlen: {}", world.positions.len());
+
+    for round in 0..opts.rounds {
+        println!("Round {}/{}: simulating {} drops", round + 1, opts.rounds, drops);
+        world.simulate_node_centered_drops(drops, blur);
+    }
+
+    if opts.rounds != 0 && drops != 0 {
+        world.fill_wetness(gen.wetness_evaporation, gen.wetness_inertia, gen.river_steps);
+    }
+
+    WorldSnapshot::capture(&world, gen.opts.subdivisions)
+        .save(&opts.out)
+        .map_err(|e| format!("could not write {}: {}", opts.out, e))?;
+
+    println!("Wrote {}", opts.out);
+
+    if let Some(prefix) = &opts.maps {
+        maps::export_maps(prefix, &world, widgets.colouring(), opts.map_width, opts.cube_size)
+            .map_err(|e| format!("could not write maps to {}: {}", prefix, e))?;
+        println!("Wrote {}_*.png", prefix);
+    }
+
+    if let Some(path) = &opts.rivers {
+        let network = RiverNetwork::new(&world, widgets.river_options.threshold);
+        geojson::write_geojson(path, &world, &network)
+            .map_err(|e| format!("could not write {}: {}", path, e))?;
+        println!("Wrote {} rivers to {}", network.rivers.len(), path);
+    }
+
+    if opts.glb.is_none() && opts.obj.is_none() && opts.ply.is_none() {
+        return Ok(());
+    }
+
+    let geometry = create_land_geometry(&world, widgets.colouring());
+    let written = |path: &String, result: std::io::Result<()>| {
+        result
+            .map(|()| println!("Wrote {}", path))
+            .map_err(|e| format!("could not write {}: {}", path, e))
+    };
+
+    if let Some(path) = &opts.glb {
+        written(path, glb::write_glb(path, &geometry, &widgets.land_material))?;
+    }
+    if let Some(path) = &opts.obj {
+        written(path, obj::export_obj(path, &geometry, &widgets.land_material))?;
+    }
+    if let Some(path) = &opts.ply {
+        written(path, ply::export_ply(path, &geometry))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,10 @@
+#![feature(portable_simd)]
+
+pub mod color;
+pub mod erosion;
+pub mod export;
+pub mod geometry;
+pub mod headless;
+pub mod rivers;
+pub mod snapshot;
+pub mod util;
@@ -1,24 +1,39 @@
 #![feature(portable_simd)]
 
-use crate::util::input_manager::InputManager;
-use crate::util::widgets;
+use sailer::util::dirty::DirtyCells;
+use sailer::util::history::WorldHistory;
+use sailer::util::bindings::Bindings;
+use sailer::util::gamepad::Gamepads;
+use sailer::util::recording;
+use sailer::util::input_manager::{InputKind, InputManager};
+use sailer::util::widgets;
 
 use glam::{Mat4, Vec3, Vec4};
 use rust_embed::RustEmbed;
 
-use crate::util::camera::Camera;
+use sailer::util::camera::{Camera, Mode as CameraMode, NEAR, VFOV};
+use sailer::util::camera::frustum::Frustum;
 use rend3::RendererProfile;
 use std::sync::Arc;
 use rend3_routine::base::BaseRenderGraph;
 
-pub mod color;
-mod geometry;
-pub mod util;
+use sailer::{color, export, geometry, headless};
 
 fn main() {
     // Setup logging
     env_logger::init();
 
+    // Generate without a window if asked to
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() == Some("--headless") {
+        let result = headless::HeadlessOpts::parse(args).and_then(headless::run);
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     // Create event loop and window
     let event_loop = winit::event_loop::EventLoop::new();
     let window = {
@@ -93,38 +108,46 @@ fn main() {
     let mut widgets = widgets::load_from_file().unwrap_or_default();
     let mut world = widgets.land_options.get_state();
 
-    println!("worldlen: {}", world.positions.len());
+    let mut history = WorldHistory::new(&world);
+    let mut erosion = None;
+    // Cells edited since the land and rivers last caught up
+    let mut dirty = DirtyCells::default();
 
-    // Create mesh and calculate smooth normals based on vertices
-    let land_mesh = geometry::land::create_land_mesh(&world, &widgets.land_palette);
+    println!("worldlen: {}", world.positions.len());
 
-    // Add mesh to renderer's world.
-    //
-    // All handles are refcounted, so we only need to hang onto the handle until we
-    // make an object.
-    let mut land_mesh_handle = renderer.add_mesh(land_mesh);
+    // Land meshes are built lazily, one per level of detail
+    let mut land_lod = geometry::lod::LandLod::new(&world, widgets.colouring(), widgets.land_shading);
 
     // Add PBR material with all defaults except a single color.
     let land_material = renderer.add_material(widgets.land_material.get_state(true));
 
-    // Combine the mesh and the material with a location to give an object.
+    // Location the land, water and clouds are all placed at.
+    let planet_transform = Mat4::from_scale(Vec3::splat(6.0));
 
-    let mut land_object = rend3::types::Object {
-        mesh_kind: rend3::types::ObjectMeshKind::Static(land_mesh_handle.clone()),
-        material: land_material.clone(),
-        transform: Mat4::from_scale(Vec3::splat(6.0)),
-    };
+    // The land is split into patches, and only those in view get an object.
+    // Objects hold onto both the mesh and the material even if they are deleted.
+    let mut land_objects = geometry::patches::LandObjects::new(land_material.clone(), planet_transform);
 
-    // Creating an object will hold onto both the mesh and the material
-    // even if they are deleted.
-    // let water = renderer.add_object(water_object);
-    let mut land = renderer.add_object(land_object.clone());
+    let mut ocean = geometry::ocean::Ocean::new(&renderer, &widgets.water_material, planet_transform);
+    let mut rivers = geometry::rivers::Rivers::new(
+        &renderer,
+        &world,
+        &widgets.river_options,
+        &widgets.river_material,
+        planet_transform,
+    );
+    let mut clouds = geometry::clouds::Clouds::new(
+        &renderer,
+        &widgets.cloud_options,
+        &widgets.cloud_material,
+        planet_transform,
+    );
 
     // Set camera's location
     renderer.set_camera_data(rend3::types::Camera {
         projection: rend3::types::CameraProjection::Perspective {
-            vfov: 60.0,
-            near: 0.1,
+            vfov: VFOV,
+            near: NEAR,
         },
         view: Mat4::IDENTITY,
     });
@@ -176,14 +199,31 @@ fn main() {
     let mut resolution = glam::UVec2::new(window_size.width, window_size.height);
 
     let mut input_manager = InputManager::new();
+    input_manager.bindings = Bindings::load_from_file();
+    let mut gamepads = Gamepads::new();
+    input_manager.screen_size = resolution.as_vec2();
     let mut camera = Camera::default();
+    // Whether the window really has the cursor grabbed, which replays don't touch
+    let mut cursor_grabbed = false;
+    camera.transition = widgets.camera_options.transition;
     let mut time = std::time::Instant::now();
+    let mut total_time = 0.0;
 
     event_loop.run(move |event, _, control| {
         match event {
             winit::event::Event::WindowEvent { event, .. }
                 if platform.on_event(&context, &event).consumed =>
             {
+                // Still let go of buttons released over egui, or drags that
+                // started in the view would never end
+                if let winit::event::WindowEvent::MouseInput {
+                    state: winit::event::ElementState::Released,
+                    button,
+                    ..
+                } = event
+                {
+                    input_manager.mouse_event(button, winit::event::ElementState::Released);
+                }
                 return
             }
 
@@ -219,6 +259,18 @@ fn main() {
             }
             // Render!
             winit::event::Event::MainEventsCleared => {
+                let mut rebuild_land = false;
+                let mut recolour_land = false;
+
+                gamepads.poll(&mut input_manager);
+
+                // Replays swap in their own input, frame time and screen size here
+                let now = std::time::Instant::now();
+                let elapsed = now.duration_since(time);
+                time = now;
+                input_manager.screen_size = resolution.as_vec2();
+                let delta = input_manager.begin_frame(elapsed.as_secs_f32());
+
                 // egui stuff
                 context.begin_frame(platform.take_egui_input(&window));
 
@@ -226,13 +278,121 @@ fn main() {
                     .resizable(true)
                     .default_open(true)
                     .show(&context, |ui| {
-                        if widgets.land_options.render_on(ui, &mut world) {
-                            let mesh = geometry::land::create_land_mesh(&world, &widgets.land_palette);
-                            land_mesh_handle = renderer.add_mesh(mesh);
-                            land_object.mesh_kind =
-                                rend3::types::ObjectMeshKind::Static(land_mesh_handle.clone());
-                            land = renderer.add_object(land_object.clone());
+                        rebuild_land |= widgets
+                            .land_options
+                            .render_on(ui, &mut world, &mut history, &mut erosion, &mut dirty);
+
+                        ui.separator();
+
+                        rebuild_land |= widgets.land_shading.render_on(ui);
+                        ui.label(format!(
+                            "Detail level {} ({} subdivisions)",
+                            land_lod.current(),
+                            land_lod.current_subdivisions(),
+                        ));
+
+                        ui.horizontal(|ui| {
+                            let glb = ui.button("Export GLB").clicked();
+                            let obj = ui.button("Export OBJ").clicked();
+                            let ply = ui.button("Export PLY").clicked();
+                            let maps = ui.button("Export Maps").clicked();
+                            let geojson = ui.button("Export Rivers").clicked();
+
+                            if !(glb || obj || ply || maps || geojson) {
+                                return;
+                            }
+
+                            let land_geometry = || geometry::land::create_land_geometry(&world, widgets.colouring());
+                            let result = if glb {
+                                ("land.glb", export::glb::write_glb("land.glb", &land_geometry(), &widgets.land_material))
+                            } else if obj {
+                                ("land.obj", export::obj::export_obj("land.obj", &land_geometry(), &widgets.land_material))
+                            } else if ply {
+                                ("land.ply", export::ply::export_ply("land.ply", &land_geometry()))
+                            } else if maps {
+                                ("land_*.png", export::maps::export_maps("land", &world, widgets.colouring(), 2048, None))
+                            } else {
+                                let network = sailer::rivers::RiverNetwork::new(&world, widgets.river_options.threshold);
+                                ("rivers.geojson", export::geojson::write_geojson("rivers.geojson", &world, &network))
+                            };
+
+                            match result {
+                                (path, Ok(())) => println!("Exported {}", path),
+                                (path, Err(e)) => println!("Could not export {}: {}", path, e),
+                            }
+                        });
+                    });
+
+                egui::Window::new("biome settings")
+                    .resizable(true)
+                    .default_open(false)
+                    .show(&context, |ui| {
+                        recolour_land |= widgets.land_biomes.render_on(ui);
+                    });
+
+                egui::Window::new("water settings")
+                    .resizable(true)
+                    .default_open(false)
+                    .show(&context, |ui| {
+                        ocean.render_on(
+                            ui,
+                            &renderer,
+                            &mut widgets.water_material,
+                            &mut widgets.water_waves,
+                        );
+                    });
+
+                egui::Window::new("river settings")
+                    .resizable(true)
+                    .default_open(false)
+                    .show(&context, |ui| {
+                        rivers.render_on(
+                            ui,
+                            &renderer,
+                            &world,
+                            &mut widgets.river_material,
+                            &mut widgets.river_options,
+                        );
+                    });
+
+                egui::Window::new("cloud settings")
+                    .resizable(true)
+                    .default_open(false)
+                    .show(&context, |ui| {
+                        clouds.render_on(
+                            ui,
+                            &renderer,
+                            &mut widgets.cloud_material,
+                            &mut widgets.cloud_options,
+                        );
+                    });
+
+                egui::Window::new("controls")
+                    .resizable(true)
+                    .default_open(false)
+                    .show(&context, |ui| {
+                        if input_manager.bindings.render_on(ui, &mut input_manager.rebinding) {
+                            input_manager.bindings.save_to_file();
                         }
+
+                        ui.separator();
+                        if widgets.camera_options.render_on(ui) {
+                            camera.transition = widgets.camera_options.transition;
+                        }
+
+                        ui.separator();
+                        recording::render_playback_on(ui, &mut input_manager, &mut camera);
+                    });
+
+                egui::Area::new("patch counter")
+                    .anchor(egui::Align2::LEFT_BOTTOM, [8.0, -8.0])
+                    .interactable(false)
+                    .show(&context, |ui| {
+                        ui.label(format!(
+                            "Land patches: {} drawn, {} culled",
+                            land_objects.drawn,
+                            land_objects.culled,
+                        ));
                     });
 
                 let egui::FullOutput {
@@ -247,19 +407,67 @@ fn main() {
                     context: context.clone(),
                 };
 
+                widgets
+                    .land_options
+                    .poll_erosion(&world, &mut history, &mut erosion, &mut dirty);
+
+                // Replays only drive the camera, so they leave the world alone
+                if erosion.is_none() && !input_manager.replaying() {
+                    if input_manager.just_pressed(InputKind::Undo) {
+                        history.undo(&world, &mut dirty);
+                    }
+                    if input_manager.just_pressed(InputKind::Redo) {
+                        history.redo(&world, &mut dirty);
+                    }
+                }
+
                 // camera stuff
+                // Clicking into the view captures the cursor for the fly
+                // camera, and Escape lets it go again. Replays set `captured`
+                // from the recording, and the real cursor is let go while they
+                // play and when they stop
+                if !input_manager.replaying() {
+                    input_manager.captured = matches!(camera.mode, CameraMode::Fly)
+                        && (input_manager.captured || input_manager.just_pressed(InputKind::Grab))
+                        && !input_manager.escape_requested;
+                }
+                let grab = input_manager.captured && !input_manager.replaying();
+                if grab != cursor_grabbed {
+                    set_cursor_capture(&window, grab);
+                    cursor_grabbed = grab;
+                }
+
                 camera.input(&input_manager);
                 input_manager.reset_frame();
-                let now = std::time::Instant::now();
-                let elapsed = now.duration_since(time);
-                time = now;
-                let view = camera.drive(elapsed.as_secs_f32());
+                let view = camera.drive(delta);
+
+                if recolour_land {
+                    land_lod.recolour(&world, widgets.colouring());
+                } else if rebuild_land {
+                    land_lod.invalidate(&world);
+                }
+
+                let rain = dirty.take_rain();
+                if let Some(edit) = dirty.take() {
+                    rivers.rebuild(&renderer, &world, &widgets.river_options);
+                    land_lod.edit(&world, edit, rain);
+                }
+
+                land_lod.select(camera.lod_distance());
+                let patches = land_lod.patches(&renderer, &world, widgets.colouring(), widgets.land_shading);
+                let frustum = Frustum::perspective(view, VFOV, NEAR, resolution.x as f32 / resolution.y as f32);
+                land_objects.update(&renderer, patches, &frustum);
+
+                total_time += delta;
+
+                ocean.animate(&renderer, &widgets.water_waves, total_time);
+                clouds.animate(&renderer, &widgets.cloud_options, &widgets.land_options.temperature, delta);
                 // println!("{:?}", view);
 
                 renderer.set_camera_data(rend3::types::Camera {
                     projection: rend3::types::CameraProjection::Perspective {
-                        vfov: 60.0,
-                        near: 0.1,
+                        vfov: VFOV,
+                        near: NEAR,
                     },
                     view,
                 });
@@ -311,17 +519,62 @@ fn main() {
                 ..
             } => {
                 input_manager.key_event(input);
-                if input_manager.escape_requested {
+                // Escape lets go of a grabbed cursor before it quits
+                if input_manager.escape_requested && !cursor_grabbed {
                     *control = winit::event_loop::ControlFlow::Exit;
                 }
             }
 
+            winit::event::Event::WindowEvent {
+                event: winit::event::WindowEvent::ModifiersChanged(modifiers),
+                ..
+            } => input_manager.modifiers_event(modifiers),
+
             winit::event::Event::WindowEvent {
                 event: winit::event::WindowEvent::MouseWheel { delta, .. },
                 ..
             } => input_manager.zoom_event(delta),
+
+            winit::event::Event::WindowEvent {
+                event: winit::event::WindowEvent::MouseInput { state, button, .. },
+                ..
+            } => input_manager.mouse_event(button, state),
+
+            winit::event::Event::WindowEvent {
+                event: winit::event::WindowEvent::CursorMoved { position, .. },
+                ..
+            } => input_manager.cursor_event(position),
+
+            winit::event::Event::WindowEvent {
+                event: winit::event::WindowEvent::CursorLeft { .. },
+                ..
+            } => input_manager.cursor_left(),
+
+            winit::event::Event::DeviceEvent {
+                event: winit::event::DeviceEvent::MouseMotion { delta },
+                ..
+            } => input_manager.motion_event(delta),
             // Other events we don't care about
             _ => {}
         }
     });
 }
+
+/// Locks and hides the cursor for mouse look, or gives it back.
+fn set_cursor_capture(window: &winit::window::Window, capture: bool) {
+    use winit::window::CursorGrabMode;
+
+    let grabbed = if capture {
+        // Not every platform can lock the cursor in place
+        window
+            .set_cursor_grab(CursorGrabMode::Locked)
+            .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined))
+    } else {
+        window.set_cursor_grab(CursorGrabMode::None)
+    };
+
+    if let Err(e) = grabbed {
+        println!("Could not grab the cursor: {}", e);
+    }
+    window.set_cursor_visible(!capture);
+}
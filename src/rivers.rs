@@ -0,0 +1,241 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use sphere_terrain::World;
+use crate::geometry::ocean::SEA_LEVEL;
+
+/// Height every filled depression is sloped by per cell, so that water can
+/// always find its way across the flats that filling leaves behind.
+const FILL_SLOPE: f32 = 1e-6;
+
+/// A cell waiting to be flooded, ordered so the lowest comes out of the heap first.
+#[derive(PartialEq)]
+struct Flood {
+    height: f32,
+    idx: usize,
+}
+
+impl Eq for Flood {}
+
+impl Ord for Flood {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.height.total_cmp(&self.height).then(other.idx.cmp(&self.idx))
+    }
+}
+
+impl PartialOrd for Flood {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Where water flows over the land, and which cells collect enough of it to
+/// be drawn as rivers.
+pub struct RiverNetwork {
+    /// Heights with every depression filled up to its spill point
+    pub filled: Vec<f32>,
+    /// Neighbour each cell drains into, `None` for the sea
+    pub downstream: Vec<Option<usize>>,
+    /// Number of cells draining through each cell, itself included
+    pub accumulation: Vec<f32>,
+    /// Cells along each river, from its source to where it meets the sea or
+    /// another river
+    pub rivers: Vec<Vec<usize>>,
+}
+
+impl RiverNetwork {
+    /// Drains `world`, keeping the rivers whose catchment is at least
+    /// `threshold` cells.
+    pub fn new(world: &World, threshold: f32) -> Self {
+        let filled = fill_depressions(world);
+        let downstream = flow_directions(world, &filled);
+        let accumulation = flow_accumulation(&filled, &downstream);
+        let rivers = extract_rivers(world, &downstream, &accumulation, threshold);
+
+        Self {
+            filled,
+            downstream,
+            accumulation,
+            rivers,
+        }
+    }
+}
+
+fn is_sea(world: &World, idx: usize) -> bool {
+    world.heights[idx].load() < SEA_LEVEL
+}
+
+/// Raises every cell that couldn't drain to the sea until it can, flooding
+/// inwards from the coast lowest cell first.
+pub fn fill_depressions(world: &World) -> Vec<f32> {
+    let len = world.positions.len();
+    let mut filled = world.heights.iter().map(|x| x.load()).collect::<Vec<_>>();
+    let mut visited = vec![false; len];
+    let mut heap = BinaryHeap::new();
+
+    for idx in (0..len).filter(|&x| is_sea(world, x)) {
+        visited[idx] = true;
+        heap.push(Flood { height: filled[idx], idx });
+    }
+
+    // A world without any sea drains into its lowest point instead
+    if heap.is_empty() && len != 0 {
+        let lowest = (0..len).min_by(|&x, &y| filled[x].total_cmp(&filled[y])).unwrap();
+        visited[lowest] = true;
+        heap.push(Flood { height: filled[lowest], idx: lowest });
+    }
+
+    while let Some(Flood { height, idx }) = heap.pop() {
+        for &next in world.adjacent[idx].iter() {
+            if visited[next] {
+                continue;
+            }
+            visited[next] = true;
+
+            filled[next] = filled[next].max(height + FILL_SLOPE);
+            heap.push(Flood { height: filled[next], idx: next });
+        }
+    }
+
+    filled
+}
+
+/// Steepest descent over the filled heights. Filling leaves every land cell
+/// with a lower neighbour, so every path ends in the sea.
+pub fn flow_directions(world: &World, filled: &[f32]) -> Vec<Option<usize>> {
+    (0..filled.len())
+        .map(|idx| {
+            if is_sea(world, idx) {
+                return None;
+            }
+
+            world.adjacent[idx]
+                .iter()
+                .copied()
+                .filter(|&x| filled[x] < filled[idx])
+                .min_by(|&x, &y| filled[x].total_cmp(&filled[y]))
+        })
+        .collect()
+}
+
+/// Counts the cells upstream of every cell, passing water down from the
+/// highest cells first.
+pub fn flow_accumulation(filled: &[f32], downstream: &[Option<usize>]) -> Vec<f32> {
+    let mut order = (0..filled.len()).collect::<Vec<_>>();
+    order.sort_by(|&x, &y| filled[y].total_cmp(&filled[x]));
+
+    let mut accumulation = vec![1.0; filled.len()];
+    for idx in order {
+        if let Some(next) = downstream[idx] {
+            accumulation[next] += accumulation[idx];
+        }
+    }
+
+    accumulation
+}
+
+/// Traces each river from a source, a land cell over the threshold with no
+/// river flowing into it, down to the sea or to a river already traced.
+pub fn extract_rivers(world: &World, downstream: &[Option<usize>], accumulation: &[f32], threshold: f32) -> Vec<Vec<usize>> {
+    let len = downstream.len();
+    let is_river = |idx: usize| !is_sea(world, idx) && accumulation[idx] >= threshold;
+
+    let mut fed = vec![false; len];
+    for idx in (0..len).filter(|&x| is_river(x)) {
+        if let Some(next) = downstream[idx] {
+            fed[next] = true;
+        }
+    }
+
+    let mut visited = vec![false; len];
+    let mut rivers = Vec::new();
+
+    for source in (0..len).filter(|&x| is_river(x) && !fed[x]) {
+        let mut river = vec![source];
+        visited[source] = true;
+
+        let mut current = source;
+        while let Some(next) = downstream[current] {
+            river.push(next);
+            if visited[next] || is_sea(world, next) {
+                break;
+            }
+            visited[next] = true;
+            current = next;
+        }
+
+        rivers.push(river);
+    }
+
+    rivers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sphere_terrain::DropSettings;
+
+    /// Sea over the southern half, rolling land over the northern half and a
+    /// pit at the north pole that can only drain by being filled.
+    fn world() -> (World, usize) {
+        let world = World::new(8, DropSettings::default());
+        for (height, position) in world.heights.iter().zip(world.positions.iter()) {
+            height.store(SEA_LEVEL + 0.1 * position.y + 0.02 * (7.0 * position.x).sin() * (5.0 * position.z).cos());
+        }
+
+        let pole = (0..world.positions.len())
+            .max_by(|&x, &y| world.positions[x].y.total_cmp(&world.positions[y].y))
+            .unwrap();
+        world.heights[pole].store(SEA_LEVEL + 0.01);
+
+        (world, pole)
+    }
+
+    #[test]
+    fn depressions_are_filled_up_to_their_spill_point() {
+        let (world, pole) = world();
+        let filled = fill_depressions(&world);
+
+        for (idx, &filled) in filled.iter().enumerate() {
+            let height = world.heights[idx].load();
+            if is_sea(&world, idx) {
+                assert_eq!(filled, height, "sea cell {} was filled", idx);
+            } else {
+                assert!(filled >= height, "cell {} was lowered", idx);
+                assert!(
+                    world.adjacent[idx].iter().any(|&x| filled[x] < filled),
+                    "cell {} has nowhere lower to drain to",
+                    idx,
+                );
+            }
+        }
+
+        // The pit rises to just above the lowest point of its rim
+        let rim = world.adjacent[pole]
+            .iter()
+            .map(|&x| filled[x])
+            .min_by(f32::total_cmp)
+            .unwrap();
+        assert!(world.heights[pole].load() < rim);
+        assert_eq!(filled[pole], rim + FILL_SLOPE);
+    }
+
+    #[test]
+    fn every_land_cell_reaches_the_sea() {
+        let (world, _) = world();
+        let filled = fill_depressions(&world);
+        let downstream = flow_directions(&world, &filled);
+
+        for source in (0..downstream.len()).filter(|&x| !is_sea(&world, x)) {
+            let mut current = source;
+            // A path longer than the world has cells would be going in circles
+            for _ in 0..downstream.len() {
+                match downstream[current] {
+                    Some(next) => current = next,
+                    None => break,
+                }
+            }
+
+            assert!(is_sea(&world, current), "cell {} drains into {}, which isn't sea", source, current);
+        }
+    }
+}
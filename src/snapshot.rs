@@ -0,0 +1,200 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use sphere_terrain::World;
+
+const MAGIC: [u8; 8] = *b"SAILWRLD";
+const VERSION: u32 = 1;
+
+/// Default location of the snapshot saved from the land settings window.
+pub const SNAPSHOT_PATH: &str = "world_snapshot";
+
+/// Most subdivisions a snapshot may have, well past what the land settings
+/// go up to, so a corrupt header can't ask for an absurd amount of memory.
+pub const MAX_SUBDIVISIONS: usize = 1024;
+
+/// Number of cells in a hexasphere with `subdivisions` subdivisions, or `None`
+/// if that doesn't fit in a `usize`.
+pub fn cell_count(subdivisions: usize) -> Option<usize> {
+    let side = subdivisions.checked_add(1)?;
+    side.checked_mul(side)?.checked_mul(10)?.checked_add(2)
+}
+
+/// Everything about a `World` that noise generation and erosion produce.
+///
+/// The file is little endian: the magic bytes, a `u32` version, `u32`
+/// subdivisions, a `u32` cell count and then the heights, hardness and
+/// wetness as `f32` arrays of that length.
+pub struct WorldSnapshot {
+    pub subdivisions: usize,
+    pub heights: Vec<f32>,
+    pub hardness: Vec<f32>,
+    pub wetness: Vec<f32>,
+}
+
+fn invalid(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+fn read_u32(input: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Reads `len` floats. The buffer grows as the data comes in, so a file that
+/// ends early fails without allocating for everything it claimed to hold.
+fn read_f32s(input: &mut impl Read, len: usize) -> std::io::Result<Vec<f32>> {
+    let size = len
+        .checked_mul(4)
+        .ok_or_else(|| invalid(format!("{} cells don't fit in memory", len)))?;
+
+    let mut bytes = Vec::new();
+    input.take(size as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != size {
+        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "snapshot ends early"));
+    }
+
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|x| f32::from_le_bytes([x[0], x[1], x[2], x[3]]))
+        .collect())
+}
+
+impl WorldSnapshot {
+    pub fn capture(world: &World, subdivisions: usize) -> Self {
+        Self {
+            subdivisions,
+            heights: world.heights.iter().map(|x| x.load()).collect(),
+            hardness: world.hardness.iter().map(|x| x.load()).collect(),
+            wetness: world.wetness.iter().map(|x| x.load()).collect(),
+        }
+    }
+
+    pub fn write(&self, mut out: impl Write) -> std::io::Result<()> {
+        out.write_all(&MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&(self.subdivisions as u32).to_le_bytes())?;
+        out.write_all(&(self.heights.len() as u32).to_le_bytes())?;
+
+        for values in [&self.heights, &self.hardness, &self.wetness] {
+            for x in values.iter() {
+                out.write_all(&x.to_le_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn read(mut input: impl Read) -> std::io::Result<Self> {
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(invalid("not a world snapshot".to_string()));
+        }
+
+        let version = read_u32(&mut input)?;
+        if version != VERSION {
+            return Err(invalid(format!("unsupported snapshot version {}", version)));
+        }
+
+        let subdivisions = read_u32(&mut input)? as usize;
+        let len = read_u32(&mut input)? as usize;
+
+        // Everything is checked before anything is allocated for the cells
+        if subdivisions > MAX_SUBDIVISIONS {
+            return Err(invalid(format!(
+                "snapshot has {} subdivisions, more than the {} allowed",
+                subdivisions, MAX_SUBDIVISIONS,
+            )));
+        }
+        if cell_count(subdivisions) != Some(len) {
+            return Err(invalid(format!(
+                "snapshot has {} cells but {} subdivisions make {}",
+                len,
+                subdivisions,
+                cell_count(subdivisions).unwrap_or(usize::MAX),
+            )));
+        }
+
+        Ok(Self {
+            subdivisions,
+            heights: read_f32s(&mut input, len)?,
+            hardness: read_f32s(&mut input, len)?,
+            wetness: read_f32s(&mut input, len)?,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write(&mut out)?;
+        out.flush()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(subdivisions: usize) -> WorldSnapshot {
+        let len = cell_count(subdivisions).unwrap();
+
+        WorldSnapshot {
+            subdivisions,
+            heights: (0..len).map(|x| 1.0 + x as f32 / len as f32).collect(),
+            hardness: (0..len).map(|x| (x % 7) as f32 / 7.0).collect(),
+            wetness: (0..len).map(|x| (x % 3) as f32).collect(),
+        }
+    }
+
+    fn written(snapshot: &WorldSnapshot) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        snapshot.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trips() {
+        let original = snapshot(3);
+        let read = WorldSnapshot::read(written(&original).as_slice()).unwrap();
+
+        assert_eq!(read.subdivisions, original.subdivisions);
+        assert_eq!(read.heights, original.heights);
+        assert_eq!(read.hardness, original.hardness);
+        assert_eq!(read.wetness, original.wetness);
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let bytes = written(&snapshot(3));
+
+        for len in [0, 4, MAGIC.len() + 4, bytes.len() / 2, bytes.len() - 1] {
+            assert!(WorldSnapshot::read(&bytes[..len]).is_err(), "read {} bytes", len);
+        }
+    }
+
+    #[test]
+    fn rejects_corrupt_headers() {
+        let bytes = written(&snapshot(3));
+        let with_header = |subdivisions: u32, len: u32| {
+            let mut bytes = bytes.clone();
+            bytes[12..16].copy_from_slice(&subdivisions.to_le_bytes());
+            bytes[16..20].copy_from_slice(&len.to_le_bytes());
+            bytes
+        };
+
+        // Counts that don't match, and ones that would need huge allocations
+        for (subdivisions, len) in [(3, 161), (4, 162), (3, u32::MAX), (u32::MAX, u32::MAX), (5000, 250_100_012)] {
+            let bytes = with_header(subdivisions, len);
+            assert!(WorldSnapshot::read(bytes.as_slice()).is_err(), "read {} subdivisions, {} cells", subdivisions, len);
+        }
+
+        let mut bytes = bytes.clone();
+        bytes[0] = b'X';
+        assert!(WorldSnapshot::read(bytes.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use egui::Ui;
+use serde::{Deserialize, Serialize};
+use winit::event::{ModifiersState, MouseButton, VirtualKeyCode};
+use crate::util::input_manager::InputKind;
+
+/// Where bindings are saved, next to `widget_settings`.
+pub const BINDINGS_PATH: &str = "input_bindings";
+
+/// Something that can be held down.
+#[derive(Serialize, Deserialize, Copy, Clone, Hash, Debug, Eq, PartialEq)]
+pub enum Trigger {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+    Gamepad(gilrs::Button),
+}
+
+impl Trigger {
+    pub fn is_modifier(&self) -> bool {
+        use VirtualKeyCode::*;
+
+        matches!(
+            self,
+            Trigger::Key(LShift | RShift | LControl | RControl | LAlt | RAlt | LWin | RWin)
+        )
+    }
+}
+
+/// A trigger, and the modifiers that have to be held along with it.
+#[derive(Serialize, Deserialize, Copy, Clone, Hash, Debug, Eq, PartialEq)]
+pub struct Binding {
+    pub trigger: Trigger,
+    #[serde(default)]
+    pub ctrl: bool,
+    #[serde(default)]
+    pub shift: bool,
+    #[serde(default)]
+    pub alt: bool,
+}
+
+impl Binding {
+    pub fn new(trigger: Trigger, modifiers: ModifiersState) -> Self {
+        Self {
+            trigger,
+            ctrl: modifiers.ctrl(),
+            shift: modifiers.shift(),
+            alt: modifiers.alt(),
+        }
+    }
+
+    fn key(code: VirtualKeyCode) -> Self {
+        Self::new(Trigger::Key(code), ModifiersState::empty())
+    }
+
+    fn ctrl(code: VirtualKeyCode) -> Self {
+        Self {
+            ctrl: true,
+            ..Self::key(code)
+        }
+    }
+
+    fn mouse(button: MouseButton) -> Self {
+        Self::new(Trigger::Mouse(button), ModifiersState::empty())
+    }
+
+    fn pad(button: gilrs::Button) -> Self {
+        Self::new(Trigger::Gamepad(button), ModifiersState::empty())
+    }
+
+    /// Whether every modifier this binding needs is held. Extra modifiers
+    /// don't matter, so Shift can be held while moving.
+    fn held(&self, modifiers: ModifiersState) -> bool {
+        (!self.ctrl || modifiers.ctrl()) && (!self.shift || modifiers.shift()) && (!self.alt || modifiers.alt())
+    }
+
+    fn modifier_count(&self) -> usize {
+        [self.ctrl, self.shift, self.alt].into_iter().filter(|&x| x).count()
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, name) in [(self.ctrl, "Ctrl"), (self.shift, "Shift"), (self.alt, "Alt")] {
+            if held {
+                write!(f, "{}+", name)?;
+            }
+        }
+
+        match self.trigger {
+            Trigger::Key(code) => write!(f, "{:?}", code),
+            Trigger::Mouse(MouseButton::Other(button)) => write!(f, "Mouse {}", button),
+            Trigger::Mouse(button) => write!(f, "Mouse {:?}", button),
+            Trigger::Gamepad(button) => write!(f, "Pad {:?}", button),
+        }
+    }
+}
+
+/// Which bindings fire each action. An action may have any number of them.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Bindings {
+    pub actions: BTreeMap<InputKind, Vec<Binding>>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use InputKind::*;
+        use VirtualKeyCode as Key;
+        use gilrs::Button as Pad;
+
+        let actions = [
+            (ArrowUp, vec![Binding::key(Key::Up), Binding::pad(Pad::DPadUp)]),
+            (ArrowDown, vec![Binding::key(Key::Down), Binding::pad(Pad::DPadDown)]),
+            (ArrowLeft, vec![Binding::key(Key::Left), Binding::pad(Pad::DPadLeft)]),
+            (ArrowRight, vec![Binding::key(Key::Right), Binding::pad(Pad::DPadRight)]),
+            (DirForward, vec![Binding::key(Key::W)]),
+            (DirRight, vec![Binding::key(Key::D)]),
+            (DirLeft, vec![Binding::key(Key::A)]),
+            (DirBack, vec![Binding::key(Key::S)]),
+            (DirDown, vec![Binding::key(Key::LShift), Binding::key(Key::RShift)]),
+            (DirUp, vec![Binding::key(Key::Space)]),
+            (CamSwitch, vec![Binding::key(Key::C), Binding::pad(Pad::North)]),
+            (Undo, vec![Binding::ctrl(Key::Z), Binding::pad(Pad::LeftTrigger)]),
+            (
+                Redo,
+                vec![
+                    Binding { shift: true, ..Binding::ctrl(Key::Z) },
+                    Binding::ctrl(Key::Y),
+                    Binding::pad(Pad::RightTrigger),
+                ],
+            ),
+            (Grab, vec![Binding::mouse(MouseButton::Left)]),
+            (Tilt, vec![Binding::mouse(MouseButton::Right), Binding::mouse(MouseButton::Middle)]),
+        ];
+
+        Self {
+            actions: actions.into_iter().collect(),
+        }
+    }
+}
+
+impl Bindings {
+    /// Reads the bindings file, falling back to the defaults if there isn't a
+    /// usable one.
+    pub fn load_from_file() -> Self {
+        let file = match std::fs::read_to_string(BINDINGS_PATH) {
+            Ok(file) => file,
+            Err(_) => return Self::default(),
+        };
+
+        match ron::from_str(&file) {
+            Ok(bindings) => bindings,
+            Err(e) => {
+                println!("Could not read {}, using the default bindings: {}", BINDINGS_PATH, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save_to_file(&self) {
+        let saved = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).unwrap();
+        if let Err(e) = std::fs::write(BINDINGS_PATH, saved) {
+            println!("Could not save {}: {}", BINDINGS_PATH, e);
+        }
+    }
+
+    /// Actions pressing `trigger` fires with `modifiers` held. Only the chords
+    /// with the most modifiers count, so Ctrl+Shift+Z doesn't also fire Ctrl+Z.
+    pub fn pressed(&self, trigger: Trigger, modifiers: ModifiersState) -> Vec<InputKind> {
+        let matching = self
+            .actions
+            .iter()
+            .flat_map(|(&kind, bindings)| bindings.iter().map(move |binding| (kind, binding)))
+            .filter(|(_, binding)| binding.trigger == trigger && binding.held(modifiers))
+            .collect::<Vec<_>>();
+
+        let most = matching
+            .iter()
+            .map(|(_, binding)| binding.modifier_count())
+            .max()
+            .unwrap_or(0);
+
+        let mut kinds = matching
+            .into_iter()
+            .filter(|(_, binding)| binding.modifier_count() == most)
+            .map(|(kind, _)| kind)
+            .collect::<Vec<_>>();
+        kinds.dedup();
+
+        kinds
+    }
+
+    /// Actions with any binding on `trigger`, whatever its modifiers.
+    pub fn bound_to(&self, trigger: Trigger) -> Vec<InputKind> {
+        self.actions
+            .iter()
+            .filter(|(_, bindings)| bindings.iter().any(|x| x.trigger == trigger))
+            .map(|(&kind, _)| kind)
+            .collect()
+    }
+
+    /// Whether any of the triggers `kind` is bound to satisfies `held`.
+    pub fn any_held(&self, kind: InputKind, held: impl Fn(Trigger) -> bool) -> bool {
+        self.actions
+            .get(&kind)
+            .map_or(false, |bindings| bindings.iter().any(|x| held(x.trigger)))
+    }
+
+    pub fn add(&mut self, kind: InputKind, binding: Binding) {
+        let bindings = self.actions.entry(kind).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    /// Lists the bindings of every action. Pressing "+" sets `rebinding`, and
+    /// the input manager binds whatever is pressed next.
+    pub fn render_on(&mut self, ui: &mut Ui, rebinding: &mut Option<InputKind>) -> bool {
+        let mut changed = false;
+
+        egui::Grid::new("bindings_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                for kind in InputKind::ALL {
+                    ui.label(format!("{:?}", kind));
+
+                    ui.horizontal(|ui| {
+                        let bindings = self.actions.entry(kind).or_default();
+                        let mut remove = None;
+
+                        for (idx, binding) in bindings.iter().enumerate() {
+                            if ui.button(binding.to_string()).on_hover_text("Click to remove").clicked() {
+                                remove = Some(idx);
+                            }
+                        }
+
+                        if let Some(idx) = remove {
+                            bindings.remove(idx);
+                            changed = true;
+                        }
+
+                        if *rebinding == Some(kind) {
+                            ui.label("Press a key or button... (Escape cancels)");
+                        } else if ui.button("+").clicked() {
+                            *rebinding = Some(kind);
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+
+        if ui.button("Reset to Defaults").clicked() {
+            *self = Self::default();
+            changed = true;
+        }
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_round_trip_through_ron() {
+        let defaults = Bindings::default();
+        let saved = ron::ser::to_string_pretty(&defaults, ron::ser::PrettyConfig::default()).unwrap();
+        let loaded: Bindings = ron::from_str(&saved).unwrap();
+
+        assert_eq!(loaded.actions, defaults.actions);
+    }
+
+    #[test]
+    fn every_action_is_listed_and_bound() {
+        use InputKind::*;
+
+        // No wildcard, so a new action doesn't compile until it's numbered here
+        let number = |kind: InputKind| match kind {
+            ArrowUp => 0,
+            ArrowDown => 1,
+            ArrowLeft => 2,
+            ArrowRight => 3,
+            DirForward => 4,
+            DirRight => 5,
+            DirLeft => 6,
+            DirBack => 7,
+            DirDown => 8,
+            DirUp => 9,
+            CamSwitch => 10,
+            Undo => 11,
+            Redo => 12,
+            Grab => 13,
+            Tilt => 14,
+        };
+
+        let mut listed = [false; 15];
+        InputKind::ALL.iter().for_each(|&x| listed[number(x)] = true);
+        assert!(listed.iter().all(|&x| x), "InputKind::ALL is missing an action");
+
+        let defaults = Bindings::default();
+        for kind in InputKind::ALL {
+            assert!(defaults.actions.get(&kind).map_or(false, |x| !x.is_empty()), "{:?} has no default binding", kind);
+        }
+    }
+}
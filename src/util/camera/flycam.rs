@@ -1,6 +1,18 @@
-use crate::util::input_manager::{InputKind, InputManager};
+use crate::util::input_manager::{InputAxis, InputKind, InputManager};
 use buttery::{Scaffold, TransformComponent, Translate};
 use glam::{Mat4, Quat, Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+/// Radians turned per unit of raw mouse movement while the cursor is captured.
+const LOOK_SENSITIVITY: f32 = 0.002;
+
+/// Current and target values of every part of a fly camera.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct FlyState {
+    pub position: [[f32; 3]; 2],
+    pub rotate: [[f32; 2]; 2],
+    pub speed: [f32; 2],
+}
 
 pub struct FlyCamera {
     position: TransformComponent<Translate<Vec3>>,
@@ -19,49 +31,73 @@ impl FlyCamera {
             .inverse()
     }
 
+    pub fn position(&self) -> Vec3 {
+        self.position.current
+    }
+
+    /// The direction the camera is currently looking.
+    pub fn forward(&self) -> Vec3 {
+        let rotation = self.rotate.current;
+
+        (Quat::from_rotation_y(rotation.y) * Quat::from_rotation_x(rotation.x)).mul_vec3(Vec3::Z)
+    }
+
+    /// Jumps to `position`, looking along `forward`. Any roll is dropped, since
+    /// the fly camera always stays level.
+    pub fn place(&mut self, position: Vec3, forward: Vec3) {
+        let forward = forward.try_normalize().unwrap_or(Vec3::Z);
+        let rotation = Vec2::new(
+            (-forward.y).clamp(-1.0, 1.0).asin(),
+            forward.x.atan2(forward.z),
+        );
+
+        self.position.current = position;
+        self.position.target = position;
+        self.rotate.current = rotation;
+        self.rotate.target = rotation;
+    }
+
+    pub fn state(&self) -> FlyState {
+        FlyState {
+            position: [self.position.current, self.position.target].map(Vec3::to_array),
+            rotate: [self.rotate.current, self.rotate.target].map(Vec2::to_array),
+            speed: [self.speed.current, self.speed.target],
+        }
+    }
+
+    pub fn restore(&mut self, state: &FlyState) {
+        [self.position.current, self.position.target] = state.position.map(Vec3::from_array);
+        [self.rotate.current, self.rotate.target] = state.rotate.map(Vec2::from_array);
+        [self.speed.current, self.speed.target] = state.speed;
+    }
+
     pub fn input(&mut self, input: &InputManager) {
         self.speed.target *= 1.0 - input.zoom / 5.0;
         let speed = self.speed.current;
         let rotation = self.rotate.current;
         let rotation = Quat::from_rotation_y(rotation.y);
 
-        let mut move_delta = Vec3::ZERO;
-        if input.pressed(InputKind::DirForward) {
-            move_delta += rotation.mul_vec3(Vec3::Z);
-        }
-        if input.pressed(InputKind::DirBack) {
-            move_delta += rotation.mul_vec3(-Vec3::Z);
-        }
-        if input.pressed(InputKind::DirRight) {
-            move_delta += rotation.mul_vec3(Vec3::X);
-        }
-        if input.pressed(InputKind::DirLeft) {
-            move_delta += rotation.mul_vec3(-Vec3::X);
-        }
-        if input.pressed(InputKind::DirUp) {
-            move_delta += rotation.mul_vec3(Vec3::Y);
-        }
-        if input.pressed(InputKind::DirDown) {
-            move_delta += rotation.mul_vec3(-Vec3::Y);
-        }
+        let move_delta = Vec3::new(
+            input.axis_or_keys(InputAxis::MoveRight, InputKind::DirRight, InputKind::DirLeft),
+            input.axis_or_keys(InputAxis::Climb, InputKind::DirUp, InputKind::DirDown),
+            input.axis_or_keys(InputAxis::MoveForward, InputKind::DirForward, InputKind::DirBack),
+        );
 
-        move_delta *= speed;
-        self.position.target += move_delta;
+        self.position.target += rotation.mul_vec3(move_delta) * speed;
 
         let off = 0.04;
 
+        let look_right = input.axis_or_keys(InputAxis::LookRight, InputKind::ArrowRight, InputKind::ArrowLeft);
+        let look_up = input.axis_or_keys(InputAxis::LookUp, InputKind::ArrowUp, InputKind::ArrowDown);
+
         let mut rotate = self.rotate.target;
-        if input.pressed(InputKind::ArrowUp) {
-            rotate.x = (rotate.x - off).max(-std::f32::consts::FRAC_PI_2);
-        }
-        if input.pressed(InputKind::ArrowDown) {
-            rotate.x = (rotate.x + off).min(std::f32::consts::FRAC_PI_2);
-        }
-        if input.pressed(InputKind::ArrowRight) {
-            rotate.y += off;
-        }
-        if input.pressed(InputKind::ArrowLeft) {
-            rotate.y -= off;
+        rotate.x = (rotate.x - look_up * off).clamp(-std::f32::consts::FRAC_PI_2, std::f32::consts::FRAC_PI_2);
+        rotate.y += look_right * off;
+
+        if input.captured {
+            rotate.x = (rotate.x + input.mouse_motion.y * LOOK_SENSITIVITY)
+                .clamp(-std::f32::consts::FRAC_PI_2, std::f32::consts::FRAC_PI_2);
+            rotate.y += input.mouse_motion.x * LOOK_SENSITIVITY;
         }
         self.rotate.target = rotate;
     }
@@ -76,3 +112,23 @@ impl Default for FlyCamera {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_forward_along_the_stick() {
+        let mut camera = FlyCamera::default();
+        let mut input = InputManager::new();
+        input.axis_event(InputAxis::MoveForward, 1.0);
+
+        let before = camera.state().position[1];
+        camera.input(&input);
+        let after = camera.state().position[1];
+
+        // Looking down +Z with no rotation, so only Z moves
+        assert!(after[2] > before[2]);
+        assert_eq!([after[0], after[1]], [before[0], before[1]]);
+    }
+}
@@ -0,0 +1,27 @@
+use glam::{Mat4, Vec3, Vec4};
+
+/// Planes bounding what the camera can see, facing inwards.
+#[derive(Debug, Copy, Clone)]
+pub struct Frustum {
+    planes: [Vec4; 5],
+}
+
+impl Frustum {
+    /// Frustum of a `CameraProjection::Perspective` with `vfov` in degrees,
+    /// matching the infinite reversed-z projection rend3 builds from it.
+    pub fn perspective(view: Mat4, vfov: f32, near: f32, aspect: f32) -> Self {
+        let clip = Mat4::perspective_infinite_reverse_lh(vfov.to_radians(), aspect, near) * view;
+        let [x, y, z, w] = [0, 1, 2, 3].map(|x| clip.row(x));
+
+        // Reversed z puts the near plane at z = w, and the far plane is at infinity
+        let planes = [w + x, w - x, w + y, w - y, w - z].map(|plane| plane / plane.truncate().length());
+
+        Self { planes }
+    }
+
+    pub fn intersects_sphere(&self, centre: Vec3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(centre) + plane.w >= -radius)
+    }
+}
@@ -1,10 +1,19 @@
-use buttery::TransformComponent;
+use buttery::{Rotate, TransformComponent, Translate};
 use crate::util::input_manager::{InputKind, InputManager};
-use glam::Mat4;
+use glam::{Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
 
 pub mod flycam;
+pub mod frustum;
 pub mod orbitcam;
 
+/// Vertical field of view of the camera, in degrees.
+pub const VFOV: f32 = 60.0;
+
+/// Distance to the camera's near plane.
+pub const NEAR: f32 = 0.1;
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
 pub enum Mode {
     Fly,
     Orbit,
@@ -16,11 +25,95 @@ impl Default for Mode {
     }
 }
 
+/// How close a blend gets to the new camera by the end of its duration,
+/// before it snaps the rest of the way.
+const BLEND_SETTLE: f32 = 0.001;
+
+/// Current and target values of a blend, and how long it has left.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+struct BlendState {
+    position: [[f32; 3]; 2],
+    rotation: [[f32; 4]; 2],
+    remaining: f32,
+}
+
+/// Eases the view from where the last camera left it to the new one.
+struct Blend {
+    position: TransformComponent<Translate<Vec3>>,
+    rotation: TransformComponent<Rotate>,
+    remaining: f32,
+}
+
+impl Blend {
+    fn new(view: Mat4, duration: f32) -> Self {
+        let (_, rotation, position) = view.inverse().to_scale_rotation_translation();
+        let smoothing = BLEND_SETTLE.powf(duration.recip());
+
+        Self {
+            position: TransformComponent::new(smoothing, position),
+            rotation: TransformComponent::new(smoothing, rotation),
+            remaining: duration,
+        }
+    }
+
+    fn state(&self) -> BlendState {
+        BlendState {
+            position: [self.position.current, self.position.target].map(Vec3::to_array),
+            rotation: [self.rotation.current, self.rotation.target].map(Quat::to_array),
+            remaining: self.remaining,
+        }
+    }
+
+    /// Picks a blend back up, easing as fast as one lasting `duration` would.
+    fn restore(state: &BlendState, duration: f32) -> Self {
+        let mut blend = Self::new(Mat4::IDENTITY, duration);
+        [blend.position.current, blend.position.target] = state.position.map(Vec3::from_array);
+        [blend.rotation.current, blend.rotation.target] = state.rotation.map(Quat::from_array);
+        blend.remaining = state.remaining;
+
+        blend
+    }
+
+    /// Moves towards `view`, or returns `None` once the blend is over.
+    fn drive(&mut self, view: Mat4, time: f32) -> Option<Mat4> {
+        self.remaining -= time;
+        if self.remaining <= 0.0 {
+            return None;
+        }
+
+        let (_, rotation, position) = view.inverse().to_scale_rotation_translation();
+        self.position.target = position;
+        self.rotation.target = rotation;
+
+        let position = self.position.drive(time);
+        let rotation = self.rotation.drive(time);
+
+        Some(Mat4::from_rotation_translation(rotation, position).inverse())
+    }
+}
+
+/// Everything that decides where the cameras go next, recorded along with the
+/// input so a replay starts from the same view.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CameraState {
+    pub mode: Mode,
+    pub flycam: flycam::FlyState,
+    pub orbcam: orbitcam::OrbitState,
+    pub transition: f32,
+    blend: Option<BlendState>,
+    view: Option<[f32; 16]>,
+}
+
 // #[derive(Default)]
 pub struct Camera {
     pub mode: Mode,
     pub flycam: flycam::FlyCamera,
     pub orbcam: orbitcam::OrbitCam,
+    /// Seconds switching between cameras takes. Zero cuts straight over
+    pub transition: f32,
+    blend: Option<Blend>,
+    /// What was last on screen, for blends to start from
+    view: Option<Mat4>,
 }
 
 impl Default for Camera {
@@ -51,25 +144,66 @@ impl Default for Camera {
                     0.01,
                     4.2,
                 ),
-            }
+            },
+            transition: 0.75,
+            blend: None,
+            view: None,
         }
     }
 }
 
 impl Camera {
     pub fn drive(&mut self, time: f32) -> Mat4 {
-        match self.mode {
+        let mut view = match self.mode {
             Mode::Fly => self.flycam.drive(time),
             Mode::Orbit => self.orbcam.drive(time),
+        };
+
+        if let Some(blend) = &mut self.blend {
+            match blend.drive(view, time) {
+                Some(blended) => view = blended,
+                None => self.blend = None,
+            }
+        }
+
+        self.view = Some(view);
+        view
+    }
+
+    pub fn state(&self) -> CameraState {
+        CameraState {
+            mode: self.mode,
+            flycam: self.flycam.state(),
+            orbcam: self.orbcam.state(),
+            transition: self.transition,
+            blend: self.blend.as_ref().map(Blend::state),
+            view: self.view.map(|x| x.to_cols_array()),
+        }
+    }
+
+    /// Puts both cameras and any blend between them back where `state` had
+    /// them.
+    pub fn restore(&mut self, state: &CameraState) {
+        self.mode = state.mode;
+        self.flycam.restore(&state.flycam);
+        self.orbcam.restore(&state.orbcam);
+        self.transition = state.transition;
+        self.blend = state.blend.map(|x| Blend::restore(&x, state.transition));
+        self.view = state.view.map(|x| Mat4::from_cols_array(&x));
+    }
+
+    /// How far away the land is, for picking its level of detail. The fly
+    /// camera is treated as always being up close.
+    pub fn lod_distance(&self) -> f32 {
+        match self.mode {
+            Mode::Fly => 0.0,
+            Mode::Orbit => self.orbcam.distance.current,
         }
     }
 
     pub fn input(&mut self, input: &InputManager) {
         if input.just_pressed(InputKind::CamSwitch) {
-            match self.mode {
-                Mode::Fly => self.mode = Mode::Orbit,
-                Mode::Orbit => self.mode = Mode::Fly,
-            }
+            self.switch();
         }
 
         match self.mode {
@@ -77,4 +211,25 @@ impl Camera {
             Mode::Orbit => self.orbcam.input(input),
         }
     }
+
+    /// Hands over to the other camera, which picks up from where this one is,
+    /// and blends the view across if there's a transition.
+    fn switch(&mut self) {
+        match self.mode {
+            Mode::Fly => {
+                self.orbcam.look_from(self.flycam.position(), self.flycam.forward());
+                self.mode = Mode::Orbit;
+            }
+            Mode::Orbit => {
+                let (_, rotation, position) = self.orbcam.view().inverse().to_scale_rotation_translation();
+                self.flycam.place(position, rotation.mul_vec3(Vec3::Z));
+                self.mode = Mode::Fly;
+            }
+        }
+
+        self.blend = self
+            .view
+            .filter(|_| self.transition > 0.0)
+            .map(|view| Blend::new(view, self.transition));
+    }
 }
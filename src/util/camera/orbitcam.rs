@@ -1,8 +1,24 @@
-use crate::util::input_manager::{InputKind, InputManager};
+use crate::util::camera::VFOV;
+use crate::util::input_manager::{InputAxis, InputKind, InputManager};
 use buttery::{Rotate, TransformComponent, Translate};
 use glam::{Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
 use std::ops::Neg;
 
+/// Closest the camera gets when taking over from a fly camera that's at or
+/// under the ground.
+const MIN_HANDOFF_DISTANCE: f32 = 0.2;
+
+/// Current and target values of every part of an orbit camera.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct OrbitState {
+    pub up: [[f32; 4]; 2],
+    pub inclination: [f32; 2],
+    pub distance: [f32; 2],
+    pub target_height: [f32; 2],
+    pub min: [f32; 2],
+}
+
 #[derive(Debug)]
 pub struct OrbitCam {
     pub up: TransformComponent<Rotate>,
@@ -17,11 +33,22 @@ impl OrbitCam {
     pub fn drive(&mut self, time: f32) -> Mat4 {
         // self.speed.drive(time);
 
-        let up = self.up.drive(time);
-        let incl = self.inclination.drive(time);
-        let dist = self.distance.drive(time);
-        let height = self.target_height.drive(time);
-        let min = self.min.drive(time);
+        self.up.drive(time);
+        self.inclination.drive(time);
+        self.distance.drive(time);
+        self.target_height.drive(time);
+        self.min.drive(time);
+
+        self.view()
+    }
+
+    /// The view from wherever the camera currently is, without moving it.
+    pub fn view(&self) -> Mat4 {
+        let up = self.up.current;
+        let incl = self.inclination.current;
+        let dist = self.distance.current;
+        let height = self.target_height.current;
+        let min = self.min.current;
 
         let arm = dist * Quat::from_rotation_x(incl).mul_vec3(Vec3::Z);
         let mut pos = Vec3::Y * height - arm;
@@ -35,63 +62,81 @@ impl OrbitCam {
         (Mat4::from_quat(up) * cam).inverse()
     }
 
-    pub fn input(&mut self, input: &InputManager) {
-        let (mut yaw, mut pitch) = (0.0, 0.0);
+    /// Jumps to orbit the point under `eye`, facing the same way as `forward`
+    /// along the ground and keeping the current inclination.
+    pub fn look_from(&mut self, eye: Vec3, forward: Vec3) {
+        let direction = eye.try_normalize().unwrap_or(Vec3::Y);
+        let base = Quat::from_rotation_arc(Vec3::Y, direction);
+
+        let local = base.inverse().mul_vec3(forward);
+        let yaw = if local.x == 0.0 && local.z == 0.0 {
+            0.0
+        } else {
+            local.x.atan2(local.z)
+        };
+
+        let up = (base * Quat::from_rotation_y(yaw)).normalize();
+        self.up.current = up;
+        self.up.target = up;
+
+        let distance = (eye.length() - self.target_height.current).max(MIN_HANDOFF_DISTANCE);
+        self.distance.current = distance;
+        self.distance.target = distance;
+    }
 
-        if input.pressed(InputKind::ArrowUp) {
-            pitch -= 1.0;
+    pub fn state(&self) -> OrbitState {
+        OrbitState {
+            up: [self.up.current, self.up.target].map(Quat::to_array),
+            inclination: [self.inclination.current, self.inclination.target],
+            distance: [self.distance.current, self.distance.target],
+            target_height: [self.target_height.current, self.target_height.target],
+            min: [self.min.current, self.min.target],
         }
+    }
 
-        if input.pressed(InputKind::ArrowDown) {
-            pitch += 1.0;
-        }
+    pub fn restore(&mut self, state: &OrbitState) {
+        [self.up.current, self.up.target] = state.up.map(Quat::from_array);
+        [self.inclination.current, self.inclination.target] = state.inclination;
+        [self.distance.current, self.distance.target] = state.distance;
+        [self.target_height.current, self.target_height.target] = state.target_height;
+        [self.min.current, self.min.target] = state.min;
+    }
 
-        if input.pressed(InputKind::ArrowLeft) {
-            yaw -= 1.0;
-        }
+    pub fn input(&mut self, input: &InputManager) {
+        let mut yaw = input.axis_or_keys(InputAxis::LookRight, InputKind::ArrowRight, InputKind::ArrowLeft) * 0.05;
+        let mut pitch = -input.axis_or_keys(InputAxis::LookUp, InputKind::ArrowUp, InputKind::ArrowDown) * 0.08;
 
-        if input.pressed(InputKind::ArrowRight) {
-            yaw += 1.0;
+        if input.pressed(InputKind::Tilt) {
+            yaw += input.cursor_delta.x * 0.005;
+            pitch += input.cursor_delta.y * 0.005;
         }
 
-        yaw *= 0.05;
-        pitch *= 0.08;
-
-        let mut delta_zoom = input.zoom;
-        if input.pressed(InputKind::DirUp) {
-            delta_zoom += 0.2;
-        } else if input.pressed(InputKind::DirDown) {
-            delta_zoom -= 0.2;
-        }
+        let delta_zoom = input.zoom + input.axis_or_keys(InputAxis::Climb, InputKind::DirUp, InputKind::DirDown) * 0.2;
 
         self.distance.target *= 1.0 + delta_zoom * 0.2;
         self.up.target = (self.up.target * Quat::from_rotation_y(yaw)).normalize();
         self.inclination.target =
             (self.inclination.target + pitch).clamp(0.0, std::f32::consts::FRAC_PI_2);
 
-        let (mut up, mut right) = (0.0, 0.0);
+        let right = -input.axis_or_keys(InputAxis::MoveRight, InputKind::DirRight, InputKind::DirLeft);
+        let up = input.axis_or_keys(InputAxis::MoveForward, InputKind::DirForward, InputKind::DirBack);
 
-        if input.pressed(InputKind::DirRight) {
-            right -= 1.0;
-        }
+        let want_distance = self.distance.current;
 
-        if input.pressed(InputKind::DirLeft) {
-            right += 1.0;
-        }
+        let speed_scl = 0.04 * ((want_distance.sqrt().neg().exp() + 1.0).recip() * 2.0 - 1.0);
+        let (mut right, mut up) = (right * speed_scl, up * speed_scl);
 
-        if input.pressed(InputKind::DirForward) {
-            up += 1.0;
-        }
+        if input.pressed(InputKind::Grab) && input.screen_size.y > 0.0 {
+            // Turn the planet by as much surface as the cursor moved over, so
+            // the point that was grabbed stays under it
+            let surface_per_pixel = 2.0 * self.distance.current * (VFOV.to_radians() / 2.0).tan() / input.screen_size.y;
+            let angle_per_pixel = surface_per_pixel / self.target_height.current;
 
-        if input.pressed(InputKind::DirBack) {
-            up -= 1.0;
+            right += input.cursor_delta.x * angle_per_pixel;
+            up += input.cursor_delta.y * angle_per_pixel;
         }
 
-        let want_distance = self.distance.current;
-
-        let speed_scl = 0.04 * ((want_distance.sqrt().neg().exp() + 1.0).recip() * 2.0 - 1.0);
-
-        let axis = Vec3::Y.cross(Vec3::new(right * -speed_scl, 0.0, up * speed_scl));
+        let axis = Vec3::Y.cross(Vec3::new(-right, 0.0, up));
         let len = axis.length();
         if len == 0.0 {
             return;
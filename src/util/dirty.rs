@@ -0,0 +1,89 @@
+/// What changed in the world since something last caught up with it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Dirty {
+    /// The world was replaced, or changed too much to tell
+    All,
+    /// Cells whose heights or wetness changed, each listed once
+    Cells(Vec<usize>),
+}
+
+/// Cells edited since the land mesh last caught up with the world. Edits mark
+/// the cells they touch as they make them, so nothing has to diff the world.
+#[derive(Default, Debug)]
+pub struct DirtyCells {
+    marked: Vec<bool>,
+    cells: Vec<usize>,
+    all: bool,
+    /// Whether an edit moved enough land to change where it rains
+    rain: bool,
+}
+
+impl DirtyCells {
+    pub fn mark(&mut self, cell: usize) {
+        if self.all {
+            return;
+        }
+
+        if cell >= self.marked.len() {
+            self.marked.resize(cell + 1, false);
+        }
+
+        if !std::mem::replace(&mut self.marked[cell], true) {
+            self.cells.push(cell);
+        }
+    }
+
+    /// Marks every cell whose value differs between `before` and `after`.
+    pub fn mark_changed(&mut self, before: impl IntoIterator<Item = f32>, after: impl IntoIterator<Item = f32>) {
+        before
+            .into_iter()
+            .zip(after)
+            .enumerate()
+            .filter(|(_, (before, after))| before != after)
+            .for_each(|(idx, _)| self.mark(idx));
+    }
+
+    pub fn mark_all(&mut self) {
+        self.all = true;
+        self.marked = Vec::new();
+        self.cells = Vec::new();
+    }
+
+    /// Asks for the rainfall to be worked out again, which is slow, so only
+    /// edits that reshape the land as a whole do.
+    pub fn mark_rain(&mut self) {
+        self.rain = true;
+    }
+
+    pub fn extend(&mut self, dirty: Dirty) {
+        match dirty {
+            Dirty::All => self.mark_all(),
+            Dirty::Cells(cells) => cells.into_iter().for_each(|x| self.mark(x)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.all && self.cells.is_empty()
+    }
+
+    /// Whether the rainfall was asked for since the last call.
+    pub fn take_rain(&mut self) -> bool {
+        std::mem::take(&mut self.rain)
+    }
+
+    /// Hands over every cell marked so far, leaving nothing marked.
+    pub fn take(&mut self) -> Option<Dirty> {
+        if std::mem::take(&mut self.all) {
+            return Some(Dirty::All);
+        }
+
+        if self.cells.is_empty() {
+            return None;
+        }
+
+        let cells = std::mem::take(&mut self.cells);
+        cells.iter().for_each(|&x| self.marked[x] = false);
+
+        Some(Dirty::Cells(cells))
+    }
+}
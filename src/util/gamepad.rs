@@ -0,0 +1,205 @@
+use std::collections::{HashMap, HashSet};
+use gilrs::{Axis, Button, EventType, Gilrs};
+use winit::event::ElementState;
+use crate::util::input_manager::{InputAxis, InputManager};
+
+/// Stick values closer to rest than this are read as zero, so worn sticks
+/// don't drift the camera.
+const DEADZONE: f32 = 0.12;
+
+/// What one controller is pushing and holding.
+#[derive(Default)]
+struct Pad {
+    axes: HashMap<InputAxis, f32>,
+    /// Left and right trigger, which together make up the climb axis
+    triggers: [f32; 2],
+    buttons: HashSet<Button>,
+}
+
+/// Feeds connected controllers into the input manager. Sticks and triggers
+/// become analog axes, and buttons go through the bindings like keys do.
+///
+/// Every controller is tracked on its own, so the axes are the sum of all of
+/// them and one being unplugged only lets go of what it was holding.
+pub struct Gamepads {
+    gilrs: Option<Gilrs>,
+    pads: HashMap<usize, Pad>,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(e) => {
+                println!("Gamepads are unavailable: {}", e);
+                None
+            }
+        };
+
+        Self {
+            gilrs,
+            pads: HashMap::new(),
+        }
+    }
+
+    pub fn poll(&mut self, input: &mut InputManager) {
+        let mut events = Vec::new();
+        if let Some(gilrs) = &mut self.gilrs {
+            while let Some(event) = gilrs.next_event() {
+                events.push((usize::from(event.id), event.event));
+            }
+        }
+
+        for (pad, event) in events {
+            match event {
+                EventType::AxisChanged(axis, value, _) => {
+                    let axis = match axis {
+                        Axis::LeftStickX => InputAxis::MoveRight,
+                        Axis::LeftStickY => InputAxis::MoveForward,
+                        Axis::RightStickX => InputAxis::LookRight,
+                        Axis::RightStickY => InputAxis::LookUp,
+                        _ => continue,
+                    };
+
+                    self.axis_changed(pad, axis, deadzone(value), input);
+                }
+                EventType::ButtonChanged(button @ (Button::LeftTrigger2 | Button::RightTrigger2), value, _) => {
+                    self.trigger_changed(pad, (button == Button::RightTrigger2) as usize, deadzone(value), input);
+                }
+                EventType::ButtonPressed(button, _) => self.button_changed(pad, button, ElementState::Pressed, input),
+                EventType::ButtonReleased(button, _) => self.button_changed(pad, button, ElementState::Released, input),
+                EventType::Disconnected => self.disconnected(pad, input),
+                _ => {}
+            }
+        }
+    }
+
+    fn axis_changed(&mut self, pad: usize, axis: InputAxis, value: f32, input: &mut InputManager) {
+        self.pads.entry(pad).or_default().axes.insert(axis, value);
+        self.publish(axis, input);
+    }
+
+    /// Moves the left (0) or right (1) trigger of `pad`.
+    fn trigger_changed(&mut self, pad: usize, trigger: usize, value: f32, input: &mut InputManager) {
+        let state = self.pads.entry(pad).or_default();
+        state.triggers[trigger] = value;
+        state.axes.insert(InputAxis::Climb, state.triggers[1] - state.triggers[0]);
+        self.publish(InputAxis::Climb, input);
+    }
+
+    /// Presses on the first controller to hold `button` down, and releases
+    /// once the last one lets go.
+    fn button_changed(&mut self, pad: usize, button: Button, state: ElementState, input: &mut InputManager) {
+        let held_elsewhere = self.held_by_others(pad, button);
+        let buttons = &mut self.pads.entry(pad).or_default().buttons;
+
+        let changed = match state {
+            ElementState::Pressed => buttons.insert(button),
+            ElementState::Released => buttons.remove(&button),
+        };
+
+        if changed && !held_elsewhere {
+            input.gamepad_event(button, state);
+        }
+    }
+
+    /// Lets go of whatever `pad` was holding, leaving the other controllers be.
+    fn disconnected(&mut self, pad: usize, input: &mut InputManager) {
+        let state = match self.pads.remove(&pad) {
+            Some(state) => state,
+            None => return,
+        };
+
+        for button in state.buttons {
+            if !self.held_by_others(pad, button) {
+                input.gamepad_event(button, ElementState::Released);
+            }
+        }
+
+        for axis in state.axes.into_keys() {
+            self.publish(axis, input);
+        }
+    }
+
+    fn held_by_others(&self, pad: usize, button: Button) -> bool {
+        self.pads
+            .iter()
+            .any(|(&id, state)| id != pad && state.buttons.contains(&button))
+    }
+
+    /// Sets `axis` to what every controller together is pushing it to.
+    fn publish(&self, axis: InputAxis, input: &mut InputManager) {
+        let value = self
+            .pads
+            .values()
+            .filter_map(|x| x.axes.get(&axis))
+            .sum::<f32>();
+
+        input.axis_event(axis, value);
+    }
+}
+
+fn deadzone(value: f32) -> f32 {
+    if value.abs() < DEADZONE {
+        0.0
+    } else {
+        value.signum() * (value.abs() - DEADZONE) / (1.0 - DEADZONE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::input_manager::InputKind;
+
+    fn gamepads() -> Gamepads {
+        Gamepads {
+            gilrs: None,
+            pads: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn unplugging_one_pad_keeps_the_others_axes() {
+        let (mut pads, mut input) = (gamepads(), InputManager::new());
+
+        pads.axis_changed(0, InputAxis::MoveRight, 0.25, &mut input);
+        pads.axis_changed(1, InputAxis::MoveRight, 0.5, &mut input);
+        pads.trigger_changed(1, 1, 0.75, &mut input);
+        assert_eq!(input.axis(InputAxis::MoveRight), 0.75);
+
+        pads.disconnected(0, &mut input);
+        assert_eq!(input.axis(InputAxis::MoveRight), 0.5);
+        assert_eq!(input.axis(InputAxis::Climb), 0.75);
+
+        pads.disconnected(1, &mut input);
+        assert_eq!(input.axis(InputAxis::MoveRight), 0.0);
+        assert_eq!(input.axis(InputAxis::Climb), 0.0);
+    }
+
+    #[test]
+    fn unplugging_releases_held_buttons() {
+        let (mut pads, mut input) = (gamepads(), InputManager::new());
+
+        pads.button_changed(0, Button::DPadUp, ElementState::Pressed, &mut input);
+        pads.button_changed(1, Button::DPadUp, ElementState::Pressed, &mut input);
+        pads.button_changed(0, Button::North, ElementState::Pressed, &mut input);
+        assert!(input.pressed(InputKind::ArrowUp));
+        assert!(input.pressed(InputKind::CamSwitch));
+
+        // The other pad is still holding the D-pad
+        pads.disconnected(0, &mut input);
+        assert!(input.pressed(InputKind::ArrowUp));
+        assert!(!input.pressed(InputKind::CamSwitch));
+
+        pads.button_changed(1, Button::DPadUp, ElementState::Released, &mut input);
+        assert!(!input.pressed(InputKind::ArrowUp));
+    }
+
+    #[test]
+    fn deadzone_rescales_past_the_edge() {
+        assert_eq!(deadzone(DEADZONE / 2.0), 0.0);
+        assert_eq!(deadzone(-1.0), -1.0);
+        assert!(deadzone(DEADZONE + 0.01) > 0.0);
+    }
+}
@@ -0,0 +1,354 @@
+use egui::Ui;
+use sphere_terrain::World;
+use crate::util::dirty::DirtyCells;
+
+/// Default memory budget for the history, its copy of the world included.
+pub const DEFAULT_MEMORY_CAP: usize = 256 * 1024 * 1024;
+
+#[derive(Copy, Clone, Debug)]
+struct CellChange {
+    idx: u32,
+    /// Height before and after the edit
+    height: [f32; 2],
+    /// Wetness before and after the edit
+    wetness: [f32; 2],
+    /// Hardness before and after the edit, which only Reset World refills
+    hardness: [f32; 2],
+}
+
+#[derive(Debug)]
+pub struct HistoryEntry {
+    pub label: String,
+    changes: Vec<CellChange>,
+}
+
+impl HistoryEntry {
+    fn memory(&self) -> usize {
+        self.label.len() + self.changes.len() * std::mem::size_of::<CellChange>()
+    }
+}
+
+/// Combines two consecutive edits, both sorted by cell, into one that goes
+/// from the state before `first` to the state after `second`.
+fn merge_changes(first: &[CellChange], second: &[CellChange]) -> Vec<CellChange> {
+    let mut merged = Vec::with_capacity(first.len().max(second.len()));
+    let (mut a, mut b) = (0, 0);
+
+    while a < first.len() && b < second.len() {
+        let (x, y) = (first[a], second[b]);
+        if x.idx < y.idx {
+            merged.push(x);
+            a += 1;
+        } else if y.idx < x.idx {
+            merged.push(y);
+            b += 1;
+        } else {
+            merged.push(CellChange {
+                idx: x.idx,
+                height: [x.height[0], y.height[1]],
+                wetness: [x.wetness[0], y.wetness[1]],
+                hardness: [x.hardness[0], y.hardness[1]],
+            });
+            a += 1;
+            b += 1;
+        }
+    }
+
+    merged.extend_from_slice(&first[a..]);
+    merged.extend_from_slice(&second[b..]);
+    merged
+}
+
+/// Undo/redo stack of world edits.
+///
+/// Each entry only keeps the cells an edit touched, alongside a full copy of
+/// the state the world is currently in to diff the next edit against.
+pub struct WorldHistory {
+    entries: Vec<HistoryEntry>,
+    /// Number of entries currently applied to the world
+    applied: usize,
+    heights: Vec<f32>,
+    wetness: Vec<f32>,
+    hardness: Vec<f32>,
+    pub memory_cap: usize,
+}
+
+impl WorldHistory {
+    pub fn new(world: &World) -> Self {
+        Self {
+            entries: Vec::new(),
+            applied: 0,
+            heights: world.heights.iter().map(|x| x.load()).collect(),
+            wetness: world.wetness.iter().map(|x| x.load()).collect(),
+            hardness: world.hardness.iter().map(|x| x.load()).collect(),
+            memory_cap: DEFAULT_MEMORY_CAP,
+        }
+    }
+
+    /// Forgets every entry, starting again from `world`.
+    pub fn clear(&mut self, world: &World) {
+        *self = Self {
+            memory_cap: self.memory_cap,
+            ..Self::new(world)
+        };
+    }
+
+    /// Bytes the history holds on to, counting the copy of the world's state
+    /// as well as the deltas, so the cap bounds the whole footprint.
+    pub fn memory(&self) -> usize {
+        self.state_memory() + self.entries.iter().map(HistoryEntry::memory).sum::<usize>()
+    }
+
+    /// Bytes of the heights, wetness and hardness kept to diff edits against.
+    fn state_memory(&self) -> usize {
+        (self.heights.len() + self.wetness.len() + self.hardness.len()) * std::mem::size_of::<f32>()
+    }
+
+    /// Stores the difference between `world` and the last recorded state.
+    ///
+    /// With `merge` set, an edit with the same label as the newest entry is
+    /// folded into it, so dragging a slider doesn't leave an entry per frame.
+    /// Edits that change the number of cells can't be diffed, so they clear
+    /// the history instead.
+    pub fn record(&mut self, label: String, world: &World, merge: bool) {
+        if world.heights.len() != self.heights.len() {
+            self.clear(world);
+            return;
+        }
+
+        let mut changes = Vec::new();
+        for idx in 0..world.heights.len() {
+            let height = world.heights[idx].load();
+            let wetness = world.wetness[idx].load();
+            let hardness = world.hardness[idx].load();
+            if height != self.heights[idx] || wetness != self.wetness[idx] || hardness != self.hardness[idx] {
+                changes.push(CellChange {
+                    idx: idx as u32,
+                    height: [self.heights[idx], height],
+                    wetness: [self.wetness[idx], wetness],
+                    hardness: [self.hardness[idx], hardness],
+                });
+                self.heights[idx] = height;
+                self.wetness[idx] = wetness;
+                self.hardness[idx] = hardness;
+            }
+        }
+
+        if changes.is_empty() {
+            return;
+        }
+
+        self.entries.truncate(self.applied);
+
+        match self.entries.last_mut() {
+            Some(last) if merge && last.label == label => {
+                last.changes = merge_changes(&last.changes, &changes);
+            }
+            _ => self.entries.push(HistoryEntry { label, changes }),
+        }
+        self.applied = self.entries.len();
+
+        while self.entries.len() > 1 && self.memory() > self.memory_cap {
+            self.entries.remove(0);
+            self.applied -= 1;
+        }
+    }
+
+    fn apply(&mut self, entry: usize, state: usize, world: &World, dirty: &mut DirtyCells) {
+        for change in &self.entries[entry].changes {
+            let idx = change.idx as usize;
+            dirty.mark(idx);
+            world.heights[idx].store(change.height[state]);
+            world.wetness[idx].store(change.wetness[state]);
+            world.hardness[idx].store(change.hardness[state]);
+            self.heights[idx] = change.height[state];
+            self.wetness[idx] = change.wetness[state];
+            self.hardness[idx] = change.hardness[state];
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.applied != 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.applied < self.entries.len()
+    }
+
+    /// Marks the cells it reverts in `dirty`. Returns true if the world changed.
+    pub fn undo(&mut self, world: &World, dirty: &mut DirtyCells) -> bool {
+        if !self.can_undo() || world.heights.len() != self.heights.len() {
+            return false;
+        }
+
+        self.applied -= 1;
+        self.apply(self.applied, 0, world, dirty);
+        true
+    }
+
+    /// Marks the cells it reapplies in `dirty`. Returns true if the world changed.
+    pub fn redo(&mut self, world: &World, dirty: &mut DirtyCells) -> bool {
+        if !self.can_redo() || world.heights.len() != self.heights.len() {
+            return false;
+        }
+
+        self.apply(self.applied, 1, world, dirty);
+        self.applied += 1;
+        true
+    }
+
+    /// Undoes or redoes until `applied` entries are applied.
+    pub fn jump_to(&mut self, applied: usize, world: &World, dirty: &mut DirtyCells) -> bool {
+        let mut changed = false;
+        while self.applied > applied && self.undo(world, dirty) {
+            changed = true;
+        }
+        while self.applied < applied && self.redo(world, dirty) {
+            changed = true;
+        }
+        changed
+    }
+
+    /// Marks the cells it changes in `dirty`. Returns true if the world changed.
+    pub fn render_on(&mut self, ui: &mut Ui, world: &World, dirty: &mut DirtyCells) -> bool {
+        let mut changed = false;
+
+        ui.horizontal(|ui| {
+            if ui.add_enabled(self.can_undo(), egui::Button::new("Undo")).clicked() {
+                changed |= self.undo(world, dirty);
+            }
+            if ui.add_enabled(self.can_redo(), egui::Button::new("Redo")).clicked() {
+                changed |= self.redo(world, dirty);
+            }
+        });
+
+        ui.label(format!(
+            "{:.1} / {:.0} MiB",
+            self.memory() as f32 / (1024.0 * 1024.0),
+            self.memory_cap as f32 / (1024.0 * 1024.0),
+        ));
+
+        let mut target = None;
+
+        egui::ScrollArea::vertical()
+            .max_height(200.0)
+            .show(ui, |ui| {
+                if ui.selectable_label(self.applied == 0, "Initial").clicked() {
+                    target = Some(0);
+                }
+                for (idx, entry) in self.entries.iter().enumerate() {
+                    let label = format!("{} ({} cells)", entry.label, entry.changes.len());
+                    if ui.selectable_label(self.applied == idx + 1, label).clicked() {
+                        target = Some(idx + 1);
+                    }
+                }
+            });
+
+        if let Some(target) = target {
+            changed |= self.jump_to(target, world, dirty);
+        }
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sphere_terrain::DropSettings;
+    use crate::util::dirty::Dirty;
+
+    fn world() -> World {
+        World::new(3, DropSettings::default())
+    }
+
+    fn heights(world: &World) -> Vec<f32> {
+        world.heights.iter().map(|x| x.load()).collect()
+    }
+
+    #[test]
+    fn only_changed_cells_are_stored() {
+        let world = world();
+        let mut history = WorldHistory::new(&world);
+
+        world.heights[3].store(1.5);
+        world.wetness[7].store(0.25);
+        history.record("Edit".to_string(), &world, false);
+
+        let changes = &history.entries[0].changes;
+        assert_eq!(changes.iter().map(|x| x.idx).collect::<Vec<_>>(), vec![3, 7]);
+
+        // Nothing changed, so nothing is recorded
+        history.record("Edit".to_string(), &world, false);
+        assert_eq!(history.entries.len(), 1);
+    }
+
+    #[test]
+    fn undo_and_redo_restore_every_field() {
+        let world = world();
+        let mut history = WorldHistory::new(&world);
+        let mut dirty = DirtyCells::default();
+        let before = heights(&world);
+        let (wetness, hardness) = (world.wetness[1].load(), world.hardness[2].load());
+
+        world.heights[0].store(2.0);
+        world.wetness[1].store(3.0);
+        world.hardness[2].store(0.5);
+        history.record("Reset World".to_string(), &world, false);
+        let after = heights(&world);
+
+        assert!(history.undo(&world, &mut dirty));
+        assert_eq!(heights(&world), before);
+        assert_eq!(world.wetness[1].load(), wetness);
+        assert_eq!(world.hardness[2].load(), hardness);
+        assert_eq!(dirty.take(), Some(Dirty::Cells(vec![0, 1, 2])));
+
+        assert!(history.redo(&world, &mut dirty));
+        assert_eq!(heights(&world), after);
+        assert_eq!(world.wetness[1].load(), 3.0);
+        assert_eq!(world.hardness[2].load(), 0.5);
+    }
+
+    #[test]
+    fn merged_edits_undo_in_one_step() {
+        let world = world();
+        let mut history = WorldHistory::new(&world);
+        let mut dirty = DirtyCells::default();
+        let before = heights(&world);
+
+        for (idx, height) in [(4, 1.0), (4, 2.0), (9, 3.0)] {
+            world.heights[idx].store(height);
+            history.record("Slider".to_string(), &world, true);
+        }
+        assert_eq!(history.entries.len(), 1);
+
+        let change = history.entries[0].changes[0];
+        assert_eq!((change.idx, change.height), (4, [before[4], 2.0]));
+
+        history.undo(&world, &mut dirty);
+        assert_eq!(heights(&world), before);
+    }
+
+    #[test]
+    fn oldest_entries_go_past_the_cap() {
+        let world = world();
+        let mut history = WorldHistory::new(&world);
+        let mut dirty = DirtyCells::default();
+        let before = heights(&world);
+
+        // Room for the state and about two single cell entries
+        history.memory_cap = history.state_memory() + 2 * ("Edit 0".len() + std::mem::size_of::<CellChange>());
+        for idx in 0..4 {
+            world.heights[idx].store(10.0);
+            history.record(format!("Edit {}", idx), &world, false);
+        }
+
+        assert_eq!(history.entries.len(), 2);
+        assert!(history.memory() <= history.memory_cap);
+        assert_eq!(history.entries[0].label, "Edit 2");
+
+        // Undoing as far as possible keeps the edits that were dropped
+        while history.undo(&world, &mut dirty) {}
+        assert_eq!(heights(&world)[..4], [10.0, 10.0, before[2], before[3]]);
+    }
+}
@@ -1,7 +1,13 @@
-use std::collections::HashSet;
-use winit::event::{ElementState, KeyboardInput, MouseScrollDelta, VirtualKeyCode};
+use std::collections::{HashMap, HashSet};
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use winit::dpi::PhysicalPosition;
+use winit::event::{ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode};
+use crate::util::bindings::{Binding, Bindings, Trigger};
+use crate::util::camera::Camera;
+use crate::util::recording::{InputFrame, Playback, Recording};
 
-#[derive(Copy, Clone, Hash, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Copy, Clone, Hash, Debug, Eq, PartialEq, Ord, PartialOrd)]
 pub enum InputKind {
     ArrowUp,
     ArrowDown,
@@ -14,6 +20,35 @@ pub enum InputKind {
     DirDown,
     DirUp,
     CamSwitch,
+    Undo,
+    Redo,
+    /// Held while dragging the planet around, or to capture the cursor in the fly camera
+    Grab,
+    /// Held while dragging to tilt the orbit camera
+    Tilt,
+}
+
+impl InputKind {
+    pub const ALL: [InputKind; 15] = {
+        use InputKind::*;
+
+        [
+            ArrowUp, ArrowDown, ArrowLeft, ArrowRight,
+            DirForward, DirRight, DirLeft, DirBack, DirDown, DirUp,
+            CamSwitch, Undo, Redo, Grab, Tilt,
+        ]
+    };
+}
+
+/// Analog inputs, each running from -1 to 1.
+#[derive(Serialize, Deserialize, Copy, Clone, Hash, Debug, Eq, PartialEq)]
+pub enum InputAxis {
+    MoveRight,
+    MoveForward,
+    /// Up and away from the planet
+    Climb,
+    LookRight,
+    LookUp,
 }
 
 #[derive(Default)]
@@ -23,6 +58,32 @@ pub struct InputManager {
     pub just_released: HashSet<InputKind>,
     pub zoom: f32,
     pub escape_requested: bool,
+    pub modifiers: ModifiersState,
+    /// Last known cursor position, in physical pixels
+    pub cursor: Option<Vec2>,
+    /// How far the cursor moved this frame, in physical pixels
+    pub cursor_delta: Vec2,
+    /// Raw mouse movement this frame, which keeps coming while the cursor is captured
+    pub mouse_motion: Vec2,
+    /// Whether the cursor is currently grabbed and hidden by the window
+    pub captured: bool,
+    /// Size of the window, in physical pixels
+    pub screen_size: Vec2,
+    /// Where each analog axis is resting. Unlike the deltas these aren't
+    /// reset every frame
+    pub axes: HashMap<InputAxis, f32>,
+    pub bindings: Bindings,
+    /// Action waiting for its next binding, set from the controls panel
+    pub rebinding: Option<InputKind>,
+    /// Triggers currently held down, so an action bound twice stays pressed
+    /// until both are let go
+    held: HashSet<Trigger>,
+    /// Modifier key pressed while rebinding, bound on its own if it's let go
+    /// before anything else is pressed
+    rebind_modifier: Option<Trigger>,
+    pub playback: Playback,
+    /// Seconds the current frame took, kept for the recording
+    frame_delta: f32,
 }
 
 impl InputManager {
@@ -31,42 +92,141 @@ impl InputManager {
     }
 
     pub fn key_event(&mut self, event: KeyboardInput) {
-        use InputKind::*;
-
         let code = match event.virtual_keycode {
             Some(x) => x,
             _ => return,
         };
 
-        let code = match code {
-            VirtualKeyCode::Up => ArrowUp,
-            VirtualKeyCode::Down => ArrowDown,
-            VirtualKeyCode::Left => ArrowLeft,
-            VirtualKeyCode::Right => ArrowRight,
-            VirtualKeyCode::W => DirForward,
-            VirtualKeyCode::D => DirRight,
-            VirtualKeyCode::S => DirBack,
-            VirtualKeyCode::A => DirLeft,
-            VirtualKeyCode::LShift | VirtualKeyCode::RShift => DirDown,
-            VirtualKeyCode::Space => DirUp,
-            VirtualKeyCode::C => CamSwitch,
-            VirtualKeyCode::Escape => {
-                self.escape_requested = true;
-                return;
+        // Escape isn't rebindable, so there's always a way out
+        if code == VirtualKeyCode::Escape {
+            // Cancels a rebind or a replay rather than quitting
+            if event.state == ElementState::Pressed {
+                if self.rebinding.take().is_some() {
+                    self.rebind_modifier = None;
+                } else if self.replaying() {
+                    self.stop_replay();
+                } else {
+                    self.escape_requested = true;
+                }
             }
-            _ => return,
-        };
+            return;
+        }
+
+        self.trigger_event(Trigger::Key(code), event.state);
+    }
+
+    pub fn modifiers_event(&mut self, modifiers: ModifiersState) {
+        self.modifiers = modifiers;
+    }
+
+    pub fn mouse_event(&mut self, button: MouseButton, state: ElementState) {
+        self.trigger_event(Trigger::Mouse(button), state);
+    }
+
+    pub fn gamepad_event(&mut self, button: gilrs::Button, state: ElementState) {
+        self.trigger_event(Trigger::Gamepad(button), state);
+    }
+
+    /// Moves an analog axis. Gamepads call this, but so can anything else
+    /// that wants to steer the camera.
+    pub fn axis_event(&mut self, axis: InputAxis, value: f32) {
+        if self.replaying() {
+            return;
+        }
+
+        self.axes.insert(axis, value.clamp(-1.0, 1.0));
+    }
+
+    pub fn clear_axes(&mut self) {
+        self.axes.clear();
+    }
+
+    fn trigger_event(&mut self, trigger: Trigger, state: ElementState) {
+        // Live input is ignored while a recording plays in its place
+        if self.replaying() {
+            return;
+        }
+
+        // Presses are taken for the binding, but releases still go through so
+        // whatever was held when the rebind started gets let go
+        if state == ElementState::Pressed && self.rebinding.is_some() {
+            self.rebind_press(trigger);
+            return;
+        }
 
-        if event.state == ElementState::Pressed {
-            self.pressed.insert(code);
-            self.just_pressed.insert(code);
+        if state == ElementState::Pressed {
+            self.held.insert(trigger);
+
+            for kind in self.bindings.pressed(trigger, self.modifiers) {
+                self.pressed.insert(kind);
+                self.just_pressed.insert(kind);
+            }
         } else {
-            self.pressed.remove(&code);
-            self.just_released.insert(code);
+            self.held.remove(&trigger);
+
+            // Releases ignore modifiers so shortcuts can't get stuck if Ctrl goes up first
+            for kind in self.bindings.bound_to(trigger) {
+                if self.bindings.any_held(kind, |x| self.held.contains(&x)) {
+                    continue;
+                }
+
+                if self.pressed.remove(&kind) {
+                    self.just_released.insert(kind);
+                }
+            }
+
+            if self.rebinding.is_some() && self.rebind_modifier == Some(trigger) {
+                self.finish_rebind(Binding::new(trigger, ModifiersState::empty()));
+            }
+        }
+    }
+
+    /// Binds the next press to the action being rebound. Modifier keys wait
+    /// until they're let go, so they can still be held for a chord.
+    fn rebind_press(&mut self, trigger: Trigger) {
+        if trigger.is_modifier() {
+            self.rebind_modifier = Some(trigger);
+        } else {
+            self.finish_rebind(Binding::new(trigger, self.modifiers));
+        }
+    }
+
+    fn finish_rebind(&mut self, binding: Binding) {
+        self.rebind_modifier = None;
+
+        if let Some(kind) = self.rebinding.take() {
+            self.bindings.add(kind, binding);
+            self.bindings.save_to_file();
         }
     }
 
+    pub fn cursor_event(&mut self, position: PhysicalPosition<f64>) {
+        let position = Vec2::new(position.x as f32, position.y as f32);
+        if let (Some(last), false) = (self.cursor, self.replaying()) {
+            self.cursor_delta += position - last;
+        }
+        self.cursor = Some(position);
+    }
+
+    /// Forgets the cursor when it leaves the window, so coming back in
+    /// elsewhere doesn't register as one big jump.
+    pub fn cursor_left(&mut self) {
+        self.cursor = None;
+    }
+
+    pub fn motion_event(&mut self, delta: (f64, f64)) {
+        if self.replaying() {
+            return;
+        }
+
+        self.mouse_motion += Vec2::new(delta.0 as f32, delta.1 as f32);
+    }
+
     pub fn zoom_event(&mut self, event: MouseScrollDelta) {
+        if self.replaying() {
+            return;
+        }
+
         self.zoom -= match event {
             MouseScrollDelta::LineDelta(_, lines) => lines / 5.0,
             MouseScrollDelta::PixelDelta(pos) => (pos.y / 3.0) as f32,
@@ -85,9 +245,197 @@ impl InputManager {
         self.just_released.contains(&key)
     }
 
+    pub fn axis(&self, axis: InputAxis) -> f32 {
+        self.axes.get(&axis).copied().unwrap_or(0.0)
+    }
+
+    /// An analog axis with a pair of actions pushing it all the way either
+    /// way, so keys and sticks drive the cameras the same way.
+    pub fn axis_or_keys(&self, axis: InputAxis, positive: InputKind, negative: InputKind) -> f32 {
+        let keys = self.pressed(positive) as i32 - self.pressed(negative) as i32;
+
+        (self.axis(axis) + keys as f32).clamp(-1.0, 1.0)
+    }
+
+    pub fn replaying(&self) -> bool {
+        matches!(self.playback, Playback::Replaying(..))
+    }
+
+    /// Starts recording from wherever `camera` is, so replays can put it back.
+    pub fn start_recording(&mut self, camera: &Camera) {
+        self.playback = Playback::Recording(Recording {
+            camera: Some(camera.state()),
+            ..Default::default()
+        });
+    }
+
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        match std::mem::take(&mut self.playback) {
+            Playback::Recording(recording) => Some(recording),
+            other => {
+                self.playback = other;
+                None
+            }
+        }
+    }
+
+    /// Plays `recording` back in place of live input, first putting `camera`
+    /// back where it was when the recording started.
+    pub fn start_replay(&mut self, recording: Recording, camera: &mut Camera) {
+        if let Some(state) = &recording.camera {
+            camera.restore(state);
+        }
+
+        self.release_all();
+        self.playback = Playback::Replaying(recording, 0);
+    }
+
+    pub fn stop_replay(&mut self) {
+        if self.replaying() {
+            self.playback = Playback::Live;
+            self.release_all();
+        }
+    }
+
+    /// Lets go of everything, so nothing stays held across the switch
+    /// between live and replayed input.
+    fn release_all(&mut self) {
+        self.pressed.clear();
+        self.held.clear();
+        self.axes.clear();
+        self.captured = false;
+        self.just_pressed.clear();
+        self.just_released.clear();
+        self.zoom = 0.0;
+        self.cursor_delta = Vec2::ZERO;
+        self.mouse_motion = Vec2::ZERO;
+    }
+
+    /// Starts a frame that took `delta` seconds. While replaying, this swaps
+    /// in the next recorded frame and returns the time it took instead, so
+    /// the camera moves exactly as it did.
+    pub fn begin_frame(&mut self, delta: f32) -> f32 {
+        let frame = match &mut self.playback {
+            Playback::Replaying(recording, next) => {
+                *next += 1;
+                recording.frames.get(*next - 1).cloned()
+            }
+            _ => {
+                self.frame_delta = delta;
+                return delta;
+            }
+        };
+
+        match frame {
+            Some(frame) => {
+                self.apply(&frame);
+                frame.delta
+            }
+            None => {
+                self.stop_replay();
+                delta
+            }
+        }
+    }
+
+    fn snapshot(&self, time: f32) -> InputFrame {
+        InputFrame {
+            time,
+            delta: self.frame_delta,
+            pressed: self.pressed.iter().copied().collect(),
+            just_pressed: self.just_pressed.iter().copied().collect(),
+            just_released: self.just_released.iter().copied().collect(),
+            zoom: self.zoom,
+            cursor_delta: self.cursor_delta.to_array(),
+            mouse_motion: self.mouse_motion.to_array(),
+            captured: self.captured,
+            screen_size: self.screen_size.to_array(),
+            axes: self.axes.iter().map(|(&axis, &value)| (axis, value)).collect(),
+        }
+    }
+
+    fn apply(&mut self, frame: &InputFrame) {
+        self.pressed = frame.pressed.iter().copied().collect();
+        self.just_pressed = frame.just_pressed.iter().copied().collect();
+        self.just_released = frame.just_released.iter().copied().collect();
+        self.zoom = frame.zoom;
+        self.cursor_delta = Vec2::from(frame.cursor_delta);
+        self.mouse_motion = Vec2::from(frame.mouse_motion);
+        self.captured = frame.captured;
+        self.screen_size = Vec2::from(frame.screen_size);
+        self.axes = frame.axes.iter().copied().collect();
+    }
+
+    /// Ends the frame, adding it to the recording first if one is running.
     pub fn reset_frame(&mut self) {
+        if let Playback::Recording(recording) = &self.playback {
+            let time = recording.duration();
+            let frame = self.snapshot(time);
+
+            if let Playback::Recording(recording) = &mut self.playback {
+                recording.frames.push(frame);
+            }
+        }
+
         self.zoom = 0.0;
+        self.cursor_delta = Vec2::ZERO;
+        self.mouse_motion = Vec2::ZERO;
+        self.escape_requested = false;
         self.just_pressed.clear();
         self.just_released.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn releases_go_through_while_rebinding() {
+        let mut input = InputManager::new();
+
+        input.mouse_event(MouseButton::Left, ElementState::Pressed);
+        input.gamepad_event(gilrs::Button::DPadUp, ElementState::Pressed);
+        assert!(input.pressed(InputKind::Grab));
+
+        input.rebinding = Some(InputKind::Undo);
+        input.mouse_event(MouseButton::Left, ElementState::Released);
+        input.gamepad_event(gilrs::Button::DPadUp, ElementState::Released);
+
+        assert!(!input.pressed(InputKind::Grab));
+        assert!(!input.pressed(InputKind::ArrowUp));
+        assert!(input.just_released(InputKind::Grab));
+        // Only a press finishes the rebind
+        assert_eq!(input.rebinding, Some(InputKind::Undo));
+    }
+
+    #[test]
+    fn axes_are_clamped() {
+        let mut input = InputManager::new();
+
+        input.axis_event(InputAxis::MoveRight, 2.5);
+        input.axis_event(InputAxis::LookUp, -3.0);
+
+        assert_eq!(input.axis(InputAxis::MoveRight), 1.0);
+        assert_eq!(input.axis(InputAxis::LookUp), -1.0);
+        assert_eq!(input.axis(InputAxis::Climb), 0.0);
+    }
+
+    #[test]
+    fn keys_add_onto_axes() {
+        let mut input = InputManager::new();
+        input.axis_event(InputAxis::MoveRight, 0.5);
+        let right = |input: &InputManager| input.axis_or_keys(InputAxis::MoveRight, InputKind::DirRight, InputKind::DirLeft);
+
+        assert_eq!(right(&input), 0.5);
+
+        input.pressed.insert(InputKind::DirRight);
+        assert_eq!(right(&input), 1.0);
+
+        input.pressed.insert(InputKind::DirLeft);
+        assert_eq!(right(&input), 0.5);
+
+        input.pressed.remove(&InputKind::DirRight);
+        assert_eq!(right(&input), -0.5);
+    }
+}
@@ -1,5 +1,10 @@
+pub mod bindings;
 pub mod camera;
+pub mod dirty;
+pub mod gamepad;
+pub mod history;
 pub mod input_manager;
+pub mod recording;
 pub mod widgets;
 
 pub fn default<T: Default>() -> T {
@@ -11,3 +16,39 @@ pub fn smoothstep(x: f32, left: f32, right: f32) -> f32 {
 
     x * x * x * (3.0 * x * (2.0 * x - 5.0) + 10.0)
 }
+
+fn hash(x: i32, y: i32, z: i32, seed: u32) -> f32 {
+    let mut h = seed
+        ^ (x as u32).wrapping_mul(0x8da6b343)
+        ^ (y as u32).wrapping_mul(0xd8163841)
+        ^ (z as u32).wrapping_mul(0xcb1ab31f);
+    h = (h ^ (h >> 13)).wrapping_mul(0x5bd1e995);
+    h ^= h >> 15;
+
+    h as f32 / u32::MAX as f32
+}
+
+/// Smoothly interpolated lattice noise in `[-1, 1]`, cheap enough to
+/// evaluate per cell whenever it's needed.
+pub fn value_noise(point: glam::Vec3, seed: u32) -> f32 {
+    let base = point.floor();
+    let t = point - base;
+    let t = t * t * (3.0 - 2.0 * t);
+    let [x, y, z] = base.to_array().map(|x| x as i32);
+
+    let corner = |dx: i32, dy: i32, dz: i32| hash(x + dx, y + dy, z + dz, seed);
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+    let near = lerp(
+        lerp(corner(0, 0, 0), corner(1, 0, 0), t.x),
+        lerp(corner(0, 1, 0), corner(1, 1, 0), t.x),
+        t.y,
+    );
+    let far = lerp(
+        lerp(corner(0, 0, 1), corner(1, 0, 1), t.x),
+        lerp(corner(0, 1, 1), corner(1, 1, 1), t.x),
+        t.y,
+    );
+
+    lerp(near, far, t.z) * 2.0 - 1.0
+}
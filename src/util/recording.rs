@@ -0,0 +1,97 @@
+use egui::Ui;
+use serde::{Deserialize, Serialize};
+use crate::util::camera::{Camera, CameraState};
+use crate::util::input_manager::{InputAxis, InputKind, InputManager};
+
+pub const RECORDING_PATH: &str = "input_recording";
+
+/// Everything the cameras and shortcuts read from the input manager in one frame.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct InputFrame {
+    /// Seconds since the recording started, at the start of this frame
+    pub time: f32,
+    /// Seconds the frame took, which replays drive the camera by
+    pub delta: f32,
+    pub pressed: Vec<InputKind>,
+    pub just_pressed: Vec<InputKind>,
+    pub just_released: Vec<InputKind>,
+    pub zoom: f32,
+    pub cursor_delta: [f32; 2],
+    pub mouse_motion: [f32; 2],
+    pub captured: bool,
+    pub screen_size: [f32; 2],
+    pub axes: Vec<(InputAxis, f32)>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Recording {
+    /// Where the cameras were when the recording started
+    #[serde(default)]
+    pub camera: Option<CameraState>,
+    pub frames: Vec<InputFrame>,
+}
+
+impl Recording {
+    /// Seconds from the start of the first frame to the end of the last.
+    pub fn duration(&self) -> f32 {
+        self.frames.last().map_or(0.0, |x| x.time + x.delta)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let saved = ron::to_string(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, saved).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        ron::from_str(&file).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Default)]
+pub enum Playback {
+    #[default]
+    Live,
+    Recording(Recording),
+    /// Replaying a recording, and the next frame to play
+    Replaying(Recording, usize),
+}
+
+/// Buttons to record the input to `RECORDING_PATH` and to play it back.
+pub fn render_playback_on(ui: &mut Ui, input: &mut InputManager, camera: &mut Camera) {
+    match &input.playback {
+        Playback::Live => {
+            ui.horizontal(|ui| {
+                if ui.button("Record").clicked() {
+                    input.start_recording(camera);
+                }
+
+                if ui.button("Replay").clicked() {
+                    match Recording::load(RECORDING_PATH) {
+                        Ok(recording) => input.start_replay(recording, camera),
+                        Err(e) => println!("Could not load {}: {}", RECORDING_PATH, e),
+                    }
+                }
+            });
+        }
+        Playback::Recording(recording) => {
+            ui.label(format!("Recording: {:.1}s", recording.duration()));
+
+            if ui.button("Stop and Save").clicked() {
+                if let Some(recording) = input.stop_recording() {
+                    match recording.save(RECORDING_PATH) {
+                        Ok(()) => println!("Saved {}", RECORDING_PATH),
+                        Err(e) => println!("Could not save {}: {}", RECORDING_PATH, e),
+                    }
+                }
+            }
+        }
+        Playback::Replaying(recording, frame) => {
+            ui.label(format!("Replaying: frame {} of {}", frame, recording.frames.len()));
+
+            if ui.button("Stop").clicked() {
+                input.stop_replay();
+            }
+        }
+    }
+}
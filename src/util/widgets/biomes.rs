@@ -0,0 +1,261 @@
+use egui::{DragValue, Ui};
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use crate::color;
+use crate::util::smoothstep;
+use crate::util::widgets::palette::Palette;
+
+/// Where biome tables are saved to and loaded from on their own.
+pub const BIOMES_PATH: &str = "biomes.ron";
+
+type Col = [f32; 3];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Biome {
+    pub name: String,
+    pub colour: Col,
+    /// Band of coldness this biome covers, hot to freezing. Elevation was
+    /// used in its place before there was a temperature
+    #[serde(alias = "height")]
+    pub coldness: [f32; 2],
+    /// Band of normalised moisture this biome covers, dry to wet
+    pub moisture: [f32; 2],
+}
+
+impl Biome {
+    fn new(name: &str, colour: Col, coldness: [f32; 2], moisture: [f32; 2]) -> Self {
+        Self {
+            name: name.to_string(),
+            colour,
+            coldness,
+            moisture,
+        }
+    }
+
+    /// Area of the `coldness` by `moisture` box that lies inside this biome.
+    fn overlap(&self, coldness: [f32; 2], moisture: [f32; 2]) -> f32 {
+        let overlap = |band: [f32; 2], with: [f32; 2]| (band[1].min(with[1]) - band[0].max(with[0])).max(0.0);
+
+        overlap(self.coldness, coldness) * overlap(self.moisture, moisture)
+    }
+
+    /// How far a point is from the bands, zero if it lies inside them.
+    fn distance(&self, coldness: f32, moisture: f32) -> f32 {
+        let distance = |band: [f32; 2], x: f32| (band[0] - x).max(x - band[1]).max(0.0);
+
+        distance(self.coldness, coldness).hypot(distance(self.moisture, moisture))
+    }
+}
+
+/// Biomes laid out over coldness and moisture, a generalisation of the
+/// Whittaker diagram `Palette` used to hardcode.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BiomeTable {
+    pub biomes: Vec<Biome>,
+    /// Size of the neighbourhood colours are averaged over, in coldness and
+    /// moisture. Borders fade over this width, which starts out as one cell
+    /// of the Whittaker grid.
+    #[serde(default = "default_blend")]
+    pub blend: [f32; 2],
+}
+
+fn default_blend() -> [f32; 2] {
+    [1.0 / 4.0, 1.0 / 6.0]
+}
+
+impl Default for BiomeTable {
+    fn default() -> Self {
+        Palette::whittaker().into()
+    }
+}
+
+impl From<Palette> for BiomeTable {
+    /// Lays the palette's 4 by 6 grid out as bands, merging neighbouring
+    /// cells of the same biome.
+    fn from(palette: Palette) -> Self {
+        let mut biomes = Vec::new();
+
+        for (row, cells) in palette.grid().iter().enumerate() {
+            let coldness = [row as f32 / 4.0, (row + 1) as f32 / 4.0];
+
+            let mut start = 0;
+            for end in 1..=cells.len() {
+                if end < cells.len() && cells[end] == cells[start] {
+                    continue;
+                }
+
+                let (name, colour) = cells[start];
+                biomes.push(Biome::new(name, colour, coldness, [start as f32 / 6.0, end as f32 / 6.0]));
+                start = end;
+            }
+        }
+
+        Self {
+            biomes,
+            blend: default_blend(),
+        }
+    }
+}
+
+impl BiomeTable {
+    pub fn render_on(&mut self, ui: &mut Ui) -> bool {
+        let mut changed = false;
+
+        ui.label("Blend (coldness / moisture)");
+        ui.horizontal(|ui| {
+            for blend in self.blend.iter_mut() {
+                changed |= ui
+                    .add(DragValue::new(blend).speed(0.005).clamp_range(0.0..=1.0))
+                    .changed();
+            }
+        });
+
+        let mut remove = None;
+
+        egui::Grid::new("biome_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Name");
+                ui.label("Colour");
+                ui.label("Coldness");
+                ui.label("Moisture");
+                ui.end_row();
+
+                for (idx, biome) in self.biomes.iter_mut().enumerate() {
+                    ui.text_edit_singleline(&mut biome.name);
+                    changed |= ui.color_edit_button_rgb(&mut biome.colour).changed();
+
+                    for band in [&mut biome.coldness, &mut biome.moisture] {
+                        ui.horizontal(|ui| {
+                            for bound in band.iter_mut() {
+                                changed |= ui
+                                    .add(DragValue::new(bound).speed(0.005).clamp_range(0.0..=1.0))
+                                    .changed();
+                            }
+                        });
+                    }
+
+                    if ui.button("Remove").clicked() {
+                        remove = Some(idx);
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if let Some(idx) = remove {
+            self.biomes.remove(idx);
+            changed = true;
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Add Biome").clicked() {
+                self.biomes.push(Biome::new("NEW BIOME", [0.5; 3], [0.0, 1.0], [0.0, 1.0]));
+                changed = true;
+            }
+
+            if ui.button("Reset to Whittaker").clicked() {
+                *self = Self::default();
+                changed = true;
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("Save Biomes").clicked() {
+                match self.save(BIOMES_PATH) {
+                    Ok(()) => println!("Saved {}", BIOMES_PATH),
+                    Err(e) => println!("Could not save {}: {}", BIOMES_PATH, e),
+                }
+            }
+
+            if ui.button("Load Biomes").clicked() {
+                match Self::load(BIOMES_PATH) {
+                    Ok(table) => {
+                        *self = table;
+                        changed = true;
+                    }
+                    Err(e) => println!("Could not load {}: {}", BIOMES_PATH, e),
+                }
+            }
+        });
+
+        changed
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let saved = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|e| e.to_string())?;
+        std::fs::write(path, saved).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        ron::from_str(&file).map_err(|e| e.to_string())
+    }
+
+    /// Colour of a cell, averaging the biomes around its coldness and
+    /// moisture. Points no biome is near take the colour of the closest one.
+    pub fn get(&self, wetness: f32, height: f32, coldness: f32, neighbouring: bool) -> Vec3 {
+        if neighbouring {
+            let weight = smoothstep(height, 0.98, 1.01);
+            return weight * color::SALMON + (1.0 - weight) * color::DARK_GRAY;
+        }
+
+        let [coldness_blend, wetness_blend] = self.blend.map(|x| x.max(1e-4) / 2.0);
+        let coldness_box = [coldness - coldness_blend, coldness + coldness_blend];
+        let wetness_box = [wetness - wetness_blend, wetness + wetness_blend];
+
+        let (colour, weight) = self
+            .biomes
+            .iter()
+            .map(|biome| (biome, biome.overlap(coldness_box, wetness_box)))
+            .fold((Vec3::ZERO, 0.0), |(colour, total), (biome, weight)| {
+                (colour + Vec3::from(biome.colour) * weight, total + weight)
+            });
+
+        if weight > 0.0 {
+            return colour / weight;
+        }
+
+        self.biomes
+            .iter()
+            .min_by(|x, y| {
+                x.distance(coldness, wetness)
+                    .total_cmp(&y.distance(coldness, wetness))
+            })
+            .map(|x| x.colour.into())
+            .unwrap_or(color::DARK_GRAY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette_cells_keep_their_colours() {
+        let palette = Palette::whittaker();
+        let mut table = BiomeTable::from(palette);
+        // Narrow enough that the middle of a cell only sees that cell
+        table.blend = [0.01; 2];
+
+        for (row, cells) in palette.grid().iter().enumerate() {
+            for (column, (name, colour)) in cells.iter().enumerate() {
+                let coldness = (row as f32 + 0.5) / 4.0;
+                let wetness = (column as f32 + 0.5) / 6.0;
+                let got = table.get(wetness, 1.0, coldness, false);
+
+                assert!(got.abs_diff_eq(Vec3::from(*colour), 1e-6), "{} at {}, {}", name, row, column);
+            }
+        }
+    }
+
+    #[test]
+    fn neighbouring_cells_of_a_biome_merge() {
+        let table = BiomeTable::from(Palette::whittaker());
+
+        // 24 cells, but snow covers three and several others two
+        assert_eq!(table.biomes.len(), 15);
+        let snow = table.biomes.iter().find(|x| x.name == "SNOW").unwrap();
+        assert_eq!(snow.coldness, [0.75, 1.0]);
+        assert_eq!(snow.moisture, [0.5, 1.0]);
+    }
+}
@@ -0,0 +1,25 @@
+use egui::Ui;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct CameraWidget {
+    /// Seconds the view takes to blend across when switching between the
+    /// fly and orbit cameras
+    pub transition: f32,
+}
+
+impl Default for CameraWidget {
+    fn default() -> Self {
+        Self {
+            transition: 0.75,
+        }
+    }
+}
+
+impl CameraWidget {
+    pub fn render_on(&mut self, ui: &mut Ui) -> bool {
+        ui.label("Camera Switch Duration");
+        ui.add(egui::Slider::new(&mut self.transition, 0.0..=3.0).suffix("s"))
+            .changed()
+    }
+}
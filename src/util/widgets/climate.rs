@@ -0,0 +1,241 @@
+use egui::Ui;
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use sphere_terrain::World;
+use crate::geometry::ocean::SEA_LEVEL;
+use crate::util::value_noise;
+
+/// How warm each cell is, from 0 (freezing) to 1 (hottest).
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct TemperatureOpts {
+    /// Use the temperature to pick biomes, otherwise elevation stands in for it
+    pub enabled: bool,
+    /// Axis the planet spins around, the poles being where it comes out
+    pub axis: [f32; 3],
+    /// Temperature at sea level on the equator
+    pub equator: f32,
+    /// Temperature at sea level on the poles
+    pub pole: f32,
+    /// Temperature lost per unit of height above sea level
+    pub lapse_rate: f32,
+    /// Strength of the noise added on top
+    pub noise: f32,
+    /// Scales points before sampling the noise
+    pub noise_scale: f32,
+    /// Seeds the noise
+    pub seed: u32,
+}
+
+impl Default for TemperatureOpts {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            axis: [0.0, 1.0, 0.0],
+            equator: 1.0,
+            pole: 0.0,
+            lapse_rate: 3.0,
+            noise: 0.08,
+            noise_scale: 4.0,
+            seed: 0,
+        }
+    }
+}
+
+impl TemperatureOpts {
+    pub fn render_on(&mut self, ui: &mut Ui) -> bool {
+        let mut changed = ui.checkbox(&mut self.enabled, "Use Temperature").changed();
+
+        ui.label("Planet Axis");
+        ui.horizontal(|ui| {
+            for component in self.axis.iter_mut() {
+                changed |= ui
+                    .add(egui::DragValue::new(component).speed(0.01).clamp_range(-1.0..=1.0))
+                    .changed();
+            }
+        });
+
+        ui.label("Equator");
+        changed |= ui
+            .add(egui::Slider::new(&mut self.equator, 0.0..=1.0))
+            .changed();
+
+        ui.label("Pole");
+        changed |= ui
+            .add(egui::Slider::new(&mut self.pole, 0.0..=1.0))
+            .changed();
+
+        ui.label("Lapse Rate");
+        changed |= ui
+            .add(egui::Slider::new(&mut self.lapse_rate, 0.0..=10.0).smart_aim(false))
+            .changed();
+
+        ui.label("Noise");
+        changed |= ui
+            .add(egui::Slider::new(&mut self.noise, 0.0..=0.5).smart_aim(false))
+            .changed();
+
+        ui.label("Noise Scale");
+        changed |= ui
+            .add(egui::Slider::new(&mut self.noise_scale, 0.1..=20.0).smart_aim(false))
+            .changed();
+
+        ui.label("Seed");
+        changed |= ui.add(egui::DragValue::new(&mut self.seed)).changed();
+
+        changed
+    }
+
+    pub fn axis(&self) -> Vec3 {
+        Vec3::from_array(self.axis).try_normalize().unwrap_or(Vec3::Y)
+    }
+
+    /// Temperature of a cell at `position` whose height is `height`.
+    pub fn temperature(&self, position: Vec3, height: f32) -> f32 {
+        let direction = position.normalize_or_zero();
+        let axis = self.axis();
+
+        // Cosine of the latitude, 1 on the equator and 0 on the poles
+        let sin_latitude = direction.dot(axis);
+        let cos_latitude = (1.0 - sin_latitude * sin_latitude).max(0.0).sqrt();
+
+        let sea_level = self.pole + (self.equator - self.pole) * cos_latitude;
+        let altitude = (height - SEA_LEVEL).max(0.0);
+        let noise = self.noise * value_noise(direction * self.noise_scale, self.seed);
+
+        (sea_level - self.lapse_rate * altitude + noise).clamp(0.0, 1.0)
+    }
+}
+
+/// Moisture blown in from the sea by prevailing winds, raining out where the
+/// air is forced up over land.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct MoistureOpts {
+    /// Use the rainfall to pick biomes, otherwise only `fill_wetness` is
+    pub enabled: bool,
+    /// Number of cells the air is moved along
+    pub steps: usize,
+    /// Fraction of the moisture in the air that rains out over flat land per step
+    pub rain_rate: f32,
+    /// Extra fraction that rains out per unit of height the air climbs
+    pub orographic: f32,
+    /// How much the rainfall counts for next to the wetness, from 0 to 1
+    pub weight: f32,
+}
+
+impl Default for MoistureOpts {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            steps: 60,
+            rain_rate: 0.02,
+            orographic: 40.0,
+            weight: 0.6,
+        }
+    }
+}
+
+impl MoistureOpts {
+    pub fn render_on(&mut self, ui: &mut Ui) -> bool {
+        let mut changed = ui
+            .checkbox(&mut self.enabled, "Use Rainfall")
+            .on_hover_text("Only colours the land, erosion and rivers keep using the wetness")
+            .changed();
+
+        ui.label("Steps");
+        changed |= ui
+            .add(egui::Slider::new(&mut self.steps, 1..=300))
+            .changed();
+
+        ui.label("Rain Rate");
+        changed |= ui
+            .add(egui::Slider::new(&mut self.rain_rate, 0.0..=0.2).smart_aim(false))
+            .changed();
+
+        ui.label("Orographic Rain");
+        changed |= ui
+            .add(egui::Slider::new(&mut self.orographic, 0.0..=200.0).smart_aim(false))
+            .changed();
+
+        ui.label("Weight");
+        changed |= ui
+            .add(egui::Slider::new(&mut self.weight, 0.0..=1.0))
+            .changed();
+
+        changed
+    }
+
+    /// Prevailing wind at `direction`, in latitude bands like the Earth's:
+    /// easterly trade winds up to 30 degrees, westerlies up to 60, and polar
+    /// easterlies beyond that.
+    pub fn wind(direction: Vec3, axis: Vec3) -> Vec3 {
+        let east = axis.cross(direction).normalize_or_zero();
+        let latitude = direction.dot(axis).clamp(-1.0, 1.0).asin().abs().to_degrees();
+
+        if (30.0..60.0).contains(&latitude) {
+            east
+        } else {
+            -east
+        }
+    }
+
+    /// Rain that falls on every cell once air saturated over the sea has been
+    /// blown `steps` cells inland.
+    pub fn rainfall(&self, world: &World, axis: Vec3) -> Vec<f32> {
+        let len = world.positions.len();
+        let heights = world
+            .heights
+            .iter()
+            .map(|x| x.load().max(SEA_LEVEL))
+            .collect::<Vec<_>>();
+
+        // Every cell hands its air to the neighbours downwind of it, weighted
+        // by how directly the wind blows towards them
+        let downwind = (0..len)
+            .map(|idx| {
+                let position = world.positions[idx];
+                let wind = Self::wind(position.normalize_or_zero(), axis);
+
+                world.adjacent[idx]
+                    .iter()
+                    .map(|&x| (x, (world.positions[x] - position).normalize_or_zero().dot(wind)))
+                    .filter(|&(_, weight)| weight > 0.0)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut moisture = vec![0.0f32; len];
+        let mut rainfall = vec![0.0f32; len];
+
+        for _ in 0..self.steps {
+            let mut incoming = vec![0.0f32; len];
+            let mut incoming_weight = vec![0.0f32; len];
+
+            for idx in 0..len {
+                if world.heights[idx].load() < SEA_LEVEL {
+                    moisture[idx] = 1.0;
+                }
+
+                let total = downwind[idx].iter().map(|x| x.1).sum::<f32>();
+                for &(to, weight) in &downwind[idx] {
+                    let rise = (heights[to] - heights[idx]).max(0.0);
+                    let rain = (self.rain_rate + self.orographic * rise).min(1.0);
+                    let weight = weight / total;
+
+                    rainfall[to] += moisture[idx] * rain * weight;
+                    incoming[to] += moisture[idx] * (1.0 - rain) * weight;
+                    incoming_weight[to] += weight;
+                }
+            }
+
+            // Average what arrives, so converging winds don't pile moisture up
+            moisture
+                .iter_mut()
+                .zip(incoming.iter().zip(incoming_weight.iter()))
+                .for_each(|(moisture, (&incoming, &weight))| {
+                    *moisture = if weight > 0.0 { incoming / weight } else { 0.0 };
+                });
+        }
+
+        rainfall
+    }
+}
@@ -0,0 +1,62 @@
+use egui::Ui;
+use serde::{Deserialize, Serialize};
+use crate::util::widgets::noise::LandOpts;
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct CloudWidget {
+    pub opts: LandOpts,
+    /// Fraction of the noise range below which cells are left clear
+    pub coverage: f32,
+    /// Radius of the cloud shell, relative to sea level
+    pub altitude: f32,
+    /// Radians per second around the planet axis
+    pub rotation_speed: f32,
+}
+
+impl Default for CloudWidget {
+    fn default() -> Self {
+        Self {
+            opts: LandOpts {
+                octaves: 6,
+                hurst_exponent: 0.8,
+                lacunarity: 2.0,
+                min: 0.0,
+                max: 1.0,
+                sample_scale: 1.4,
+                seed: 7,
+                subdivisions: 60,
+            },
+            coverage: 0.55,
+            altitude: 1.12,
+            rotation_speed: 0.01,
+        }
+    }
+}
+
+impl CloudWidget {
+    /// Returns true if the shell has to be regenerated.
+    pub fn render_on(&mut self, ui: &mut Ui) -> bool {
+        let mut changed = false;
+        ui.collapsing(
+            "Noise",
+            |ui| {
+                changed |= self.opts.render_on(ui, None);
+            }
+        );
+
+        ui.label("Coverage");
+        changed |= ui
+            .add(egui::Slider::new(&mut self.coverage, 0.0..=1.0).smart_aim(false))
+            .changed();
+
+        ui.label("Altitude");
+        changed |= ui
+            .add(egui::Slider::new(&mut self.altitude, 1.0..=1.5).smart_aim(false))
+            .changed();
+
+        ui.label("Rotation Speed");
+        ui.add(egui::Slider::new(&mut self.rotation_speed, -0.2..=0.2).smart_aim(false));
+
+        changed
+    }
+}
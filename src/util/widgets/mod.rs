@@ -1,31 +1,56 @@
+use crate::geometry::land::Colouring;
 use crate::util::default;
+use crate::util::widgets::biomes::BiomeTable;
+use crate::util::widgets::camera::CameraWidget;
+use crate::util::widgets::clouds::CloudWidget;
 use crate::util::widgets::material::PbrMaterialWidget;
 use crate::util::widgets::noise::LandOpts;
-// use crate::util::widgets::waves::WaterWavesWidget;
+use crate::util::widgets::rivers::RiverWidget;
+use crate::util::widgets::shading::LandShading;
+use crate::util::widgets::waves::WaterWavesWidget;
 use crate::util::widgets::worldgen::WorldGenWidget;
 use ron::ser::PrettyConfig;
-use serde::{Deserialize, Serialize};
-use crate::util::widgets::palette::Palette;
+use serde::{Deserialize, Deserializer, Serialize};
 
+pub mod biomes;
+pub mod camera;
+pub mod climate;
+pub mod clouds;
 pub mod material;
 pub mod noise;
 pub mod worldgen;
 pub mod palette;
+pub mod rivers;
+pub mod shading;
+pub mod waves;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Widgets {
     #[serde(default)]
     pub water_material: PbrMaterialWidget,
-    // #[serde(default)]
-    // pub water_waves: WaterWavesWidget,
+    #[serde(default)]
+    pub water_waves: WaterWavesWidget,
     #[serde(default)]
     pub land_material: PbrMaterialWidget,
     #[serde(default)]
     pub cloud_material: PbrMaterialWidget,
     #[serde(default)]
+    pub cloud_options: CloudWidget,
+    #[serde(default)]
+    pub river_material: PbrMaterialWidget,
+    #[serde(default)]
+    pub river_options: RiverWidget,
+    #[serde(default)]
     pub land_options: WorldGenWidget,
     #[serde(default)]
-    pub land_palette: Palette,
+    pub land_biomes: BiomeTable,
+    /// Only read from settings saved before biome tables, see `load_from_path`
+    #[serde(default, skip_serializing, deserialize_with = "deserialize_some")]
+    pub land_palette: Option<palette::Palette>,
+    #[serde(default)]
+    pub land_shading: LandShading,
+    #[serde(default)]
+    pub camera_options: CameraWidget,
     pub should_save: bool,
 }
 
@@ -33,16 +58,32 @@ impl Default for Widgets {
     fn default() -> Self {
         Self {
             water_material: default(),
-            // water_waves: default(),
+            water_waves: default(),
             land_material: default(),
             cloud_material: default(),
+            cloud_options: default(),
+            river_material: default(),
+            river_options: default(),
             land_options: default(),
-            land_palette: default(),
+            land_biomes: default(),
+            land_palette: None,
+            land_shading: default(),
+            camera_options: default(),
             should_save: true,
         }
     }
 }
 
+impl Widgets {
+    pub fn colouring(&self) -> Colouring {
+        Colouring {
+            biomes: &self.land_biomes,
+            temperature: &self.land_options.temperature,
+            moisture: &self.land_options.moisture,
+        }
+    }
+}
+
 impl Drop for Widgets {
     fn drop(&mut self) {
         if self.should_save {
@@ -51,12 +92,75 @@ impl Drop for Widgets {
     }
 }
 
+/// Reads a bare value into an `Option`, which ron would otherwise want
+/// written as `Some(...)`.
+fn deserialize_some<'de, D: Deserializer<'de>, T: Deserialize<'de>>(deserializer: D) -> Result<Option<T>, D::Error> {
+    T::deserialize(deserializer).map(Some)
+}
+
 pub fn load_from_file() -> Option<Widgets> {
-    let file = std::fs::read_to_string("widget_settings").ok()?;
-    Some(ron::from_str::<Widgets>(&file).ok()?)
+    load_from_path("widget_settings")
+}
+
+pub fn load_from_path(path: &str) -> Option<Widgets> {
+    let file = std::fs::read_to_string(path).ok()?;
+    from_ron(&file)
+}
+
+/// Reads settings saved by any version, moving an old `land_palette` over to
+/// the biome table.
+fn from_ron(saved: &str) -> Option<Widgets> {
+    let mut widgets = ron::from_str::<Widgets>(saved).ok()?;
+
+    if let Some(palette) = widgets.land_palette.take() {
+        widgets.land_biomes = palette.into();
+    }
+
+    Some(widgets)
 }
 
 pub fn save_to_file(widgets: &Widgets) {
     let saved = ron::ser::to_string_pretty(widgets, PrettyConfig::default()).unwrap();
     std::fs::write("widget_settings", saved).unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A palette as settings saved before biome tables held it.
+    const OLD_PALETTE: &str = "(
+        snow: (1.0, 1.0, 1.0),
+        tundra: (0.9, 0.9, 0.8),
+        bare: (0.8, 0.8, 0.8),
+        scorched: (0.6, 0.6, 0.6),
+        taiga: (0.1, 0.3, 0.2),
+        shrubland: (0.4, 0.5, 0.3),
+        temperate_desert: (0.9, 0.8, 0.5),
+        temperate_rainforest: (0.0, 0.4, 0.2),
+        temperate_deciduous_forest: (0.1, 0.5, 0.1),
+        grassland: (0.5, 0.7, 0.3),
+        tropical_rainforest: (0.0, 0.3, 0.1),
+        tropical_seasonal_forest: (0.2, 0.6, 0.2),
+        subtropical_desert: (0.9, 0.7, 0.4),
+    )";
+
+    #[test]
+    fn old_palettes_become_biome_tables() {
+        let saved = format!("(land_palette: {}, should_save: false)", OLD_PALETTE);
+        let widgets = from_ron(&saved).unwrap();
+        assert!(widgets.land_palette.is_none());
+
+        let expected = BiomeTable::from(ron::from_str::<palette::Palette>(OLD_PALETTE).unwrap());
+        let biome = |x: &biomes::Biome| (x.name.clone(), x.colour, x.coldness, x.moisture);
+        assert_eq!(
+            widgets.land_biomes.biomes.iter().map(biome).collect::<Vec<_>>(),
+            expected.biomes.iter().map(biome).collect::<Vec<_>>(),
+        );
+        assert_eq!(widgets.land_biomes.biomes.iter().find(|x| x.name == "SNOW").unwrap().colour, [1.0; 3]);
+
+        // Saving drops the palette, so it isn't migrated over the biomes again
+        let saved = ron::ser::to_string_pretty(&widgets, PrettyConfig::default()).unwrap();
+        assert!(!saved.contains("land_palette"));
+    }
+}
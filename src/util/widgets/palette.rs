@@ -1,11 +1,9 @@
-use egui::Ui;
 use serde::{Deserialize, Serialize};
-use glam::Vec3;
-use crate::color;
-use crate::util::smoothstep;
 
 type Col = [f32; 3];
 
+/// The fixed Whittaker palette settings used to be saved as, kept so old
+/// `land_palette` entries still load. See `BiomeTable` for what replaced it.
 #[derive(Serialize, Deserialize, Copy, Clone, Debug, Default)]
 pub struct Palette {
     snow: Col,
@@ -27,180 +25,56 @@ pub struct Palette {
 }
 
 impl Palette {
-    pub fn render_on(&mut self, ui: &mut Ui) -> bool {
-        let mut changed = false;
-        egui::Grid::new("palette_grid")
-            .show(ui, |ui| {
-                ui.horizontal_centered(|ui| {
-                    ui.label("Moisture / Elevation");
-                });
-                ui.horizontal_centered(|ui| {
-                    ui.label("6");
-                    ui.small("(wet)");
-                });
-                for i in (2..=5).rev() {
-                    ui.label(&format!("{}", i));
-                }
-                ui.horizontal_centered(|ui| {
-                    ui.label("1");
-                    ui.small("(dry)");
-                });
-                ui.end_row();
-
-                ui.horizontal_centered(|ui| {
-                    ui.label("4");
-                    ui.small("(high)");
-                });
-
-                for _ in 0..3 {
-                    ui.horizontal_centered(|ui| {
-                        ui.label("SNOW");
-                        changed |= ui.color_edit_button_rgb(&mut self.snow).changed();
-                    });
-                }
-
-                ui.horizontal_centered(|ui| {
-                    ui.label("TUNDRA");
-                    changed |= ui.color_edit_button_rgb(&mut self.tundra).changed();
-                });
-
-                ui.horizontal_centered(|ui| {
-                    ui.label("BARE");
-                    changed |= ui.color_edit_button_rgb(&mut self.bare).changed();
-                });
-
-                ui.horizontal_centered(|ui| {
-                    ui.label("SCORCHED");
-                    changed |= ui.color_edit_button_rgb(&mut self.scorched).changed();
-                });
-                ui.end_row();
-
-                ui.label("3");
-
-                for _ in 0..2 {
-                    ui.horizontal_centered(|ui| {
-                        ui.label("TAIGA");
-                        changed |= ui.color_edit_button_rgb(&mut self.taiga).changed();
-                    });
-                }
-
-                for _ in 0..2 {
-                    ui.horizontal_centered(|ui| {
-                        ui.label("SHURBLAND");
-                        changed |= ui.color_edit_button_rgb(&mut self.shrubland).changed();
-                    });
-                }
-
-                for _ in 0..2 {
-                    ui.horizontal_centered(|ui| {
-                        ui.label("TEMPERATE DESERT");
-                        changed |= ui.color_edit_button_rgb(&mut self.temperate_desert).changed();
-                    });
-                }
-                ui.end_row();
-
-                ui.label("2");
-
-                ui.horizontal_centered(|ui| {
-                    ui.label("TEMPERATE RF");
-                    changed |= ui.color_edit_button_rgb(&mut self.temperate_rainforest).changed();
-                });
-
-                for _ in 0..2 {
-                    ui.horizontal_centered(|ui| {
-                        ui.label("TEMPERATE DF");
-                        changed |= ui.color_edit_button_rgb(&mut self.temperate_deciduous_forest).changed();
-                    });
-                }
-
-                for _ in 0..2 {
-                    ui.horizontal_centered(|ui| {
-                        ui.label("GRASSLAND");
-                        changed |= ui.color_edit_button_rgb(&mut self.grassland).changed();
-                    });
-                }
-
-                ui.horizontal_centered(|ui| {
-                    ui.label("TEMPERATE DESERT");
-                    changed |= ui.color_edit_button_rgb(&mut self.temperate_desert).changed();
-                });
-                ui.end_row();
-
-                ui.horizontal_centered(|ui| {
-                    ui.label("1");
-                    ui.small("(low)");
-                });
-
-                for _ in 0..2 {
-                    ui.horizontal_centered(|ui| {
-                        ui.label("TROPICAL RF");
-                        changed |= ui.color_edit_button_rgb(&mut self.tropical_rainforest).changed();
-                    });
-                }
-
-                for _ in 0..2 {
-                    ui.horizontal_centered(|ui| {
-                        ui.label("TROPICAL SF");
-                        changed |= ui.color_edit_button_rgb(&mut self.tropical_seasonal_forest).changed();
-                    });
-                }
-
-                ui.horizontal_centered(|ui| {
-                    ui.label("GRASSLAND");
-                    changed |= ui.color_edit_button_rgb(&mut self.grassland).changed();
-                });
-
-                ui.horizontal_centered(|ui| {
-                    ui.label("SUBTROPICAL DESERT");
-                    changed |= ui.color_edit_button_rgb(&mut self.subtropical_desert).changed();
-                });
-                ui.end_row();
-            });
-
-        changed
+    /// The colours the checked in `widget_settings` had before biome tables,
+    /// which new biome tables start from.
+    pub fn whittaker() -> Self {
+        Self {
+            snow: [0.7122709, 0.8328288, 0.9466892],
+            tundra: [0.42358792, 0.4600803, 0.67903405],
+            bare: [0.6952381, 0.7039455, 1.0],
+            scorched: [0.5299908, 0.86773646, 0.96780926],
+            taiga: [0.40197787, 0.30331552, 0.08805229],
+            shrubland: [0.22418422, 0.4826465, 0.10572256],
+            temperate_desert: [0.13872907, 0.23674446, 0.034948],
+            temperate_rainforest: [0.059270088, 0.32413316, 0.043217752],
+            temperate_deciduous_forest: [0.020241024, 0.11069306, 0.014759074],
+            grassland: [0.02760486, 0.22296233, 0.044349797],
+            tropical_rainforest: [0.054223806, 0.21404114, 0.014269409],
+            tropical_seasonal_forest: [0.13866432, 0.31301698, 0.047697823],
+            subtropical_desert: [0.1295554, 0.070330046, 0.0],
+        }
     }
 
-    pub fn get(&self, wetness: f32, height: f32, height_scale: f32, neighbouring: bool) -> Vec3 {
-        if neighbouring {
-            let weight = smoothstep(height, 0.98, 1.01);
-            weight * color::SALMON + (1.0 - weight) * color::DARK_GRAY
-        } else {
-            let palette = [
-                [
-                    self.subtropical_desert, self.grassland, self.tropical_seasonal_forest, self.tropical_seasonal_forest, self.tropical_rainforest, self.tropical_rainforest,
-                ],
-                [
-                    self.temperate_desert, self.grassland, self.grassland, self.temperate_deciduous_forest, self.temperate_deciduous_forest, self.temperate_rainforest,
-                ],
-                [
-                    self.temperate_desert, self.temperate_desert, self.shrubland, self.shrubland, self.taiga, self.taiga,
-                ],
-                [
-                    self.scorched, self.bare, self.tundra, self.snow, self.snow, self.snow,
-                ],
-            ];
-
-            let wetness_scale = wetness * 6.0;
-            let height_scale = height_scale * height_scale * 4.0;
-
-            let upper_wet = (wetness_scale.ceil() as usize).min(5);
-            let lower_wet = (wetness_scale.floor() as usize).min(5);
-            let upper_height = (height_scale.ceil() as usize).min(3);
-            let lower_height = (height_scale.floor() as usize).min(3);
-
-            let top_left: Vec3 = palette[upper_height][lower_wet].into();
-            let top_right: Vec3 = palette[upper_height][upper_wet].into();
-            let bottom_left: Vec3 = palette[lower_height][lower_wet].into();
-            let bottom_right: Vec3 = palette[lower_height][upper_wet].into();
-
-            let wet_dist = wetness_scale - wetness_scale.floor();
-
-            let top = top_left * (1.0 - wet_dist) + top_right * wet_dist;
-            let bottom = bottom_left * (1.0 - wet_dist) + bottom_right * wet_dist;
-
-            let height_dist = height_scale - height_scale.floor();
-
-            bottom * (1.0 - height_dist) + top * height_dist
-        }
+    /// The Whittaker table, by elevation from low to high and then moisture
+    /// from dry to wet.
+    pub fn grid(&self) -> [[(&'static str, Col); 6]; 4] {
+        let subtropical_desert = ("SUBTROPICAL DESERT", self.subtropical_desert);
+        let grassland = ("GRASSLAND", self.grassland);
+        let tropical_seasonal_forest = ("TROPICAL SF", self.tropical_seasonal_forest);
+        let tropical_rainforest = ("TROPICAL RF", self.tropical_rainforest);
+        let temperate_desert = ("TEMPERATE DESERT", self.temperate_desert);
+        let temperate_deciduous_forest = ("TEMPERATE DF", self.temperate_deciduous_forest);
+        let temperate_rainforest = ("TEMPERATE RF", self.temperate_rainforest);
+        let shrubland = ("SHRUBLAND", self.shrubland);
+        let taiga = ("TAIGA", self.taiga);
+        let scorched = ("SCORCHED", self.scorched);
+        let bare = ("BARE", self.bare);
+        let tundra = ("TUNDRA", self.tundra);
+        let snow = ("SNOW", self.snow);
+
+        [
+            [
+                subtropical_desert, grassland, tropical_seasonal_forest, tropical_seasonal_forest, tropical_rainforest, tropical_rainforest,
+            ],
+            [
+                temperate_desert, grassland, grassland, temperate_deciduous_forest, temperate_deciduous_forest, temperate_rainforest,
+            ],
+            [
+                temperate_desert, temperate_desert, shrubland, shrubland, taiga, taiga,
+            ],
+            [
+                scorched, bare, tundra, snow, snow, snow,
+            ],
+        ]
     }
 }
@@ -0,0 +1,48 @@
+use egui::Ui;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct RiverWidget {
+    pub enabled: bool,
+    /// Cells that have to drain through a cell before it counts as a river
+    pub threshold: f32,
+    /// Width of a river at the threshold, in planet radii. Bigger rivers widen
+    /// with the square root of their catchment
+    pub width: f32,
+    /// Height the ribbons float above the land, in planet radii
+    pub lift: f32,
+}
+
+impl Default for RiverWidget {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 60.0,
+            width: 0.002,
+            lift: 0.001,
+        }
+    }
+}
+
+impl RiverWidget {
+    pub fn render_on(&mut self, ui: &mut Ui) -> bool {
+        let mut changed = ui.checkbox(&mut self.enabled, "Show Rivers").changed();
+
+        ui.label("Catchment Threshold");
+        changed |= ui
+            .add(egui::Slider::new(&mut self.threshold, 2.0..=2000.0).logarithmic(true))
+            .changed();
+
+        ui.label("Width");
+        changed |= ui
+            .add(egui::Slider::new(&mut self.width, 0.0..=0.02).smart_aim(false))
+            .changed();
+
+        ui.label("Lift");
+        changed |= ui
+            .add(egui::Slider::new(&mut self.lift, 0.0..=0.01).smart_aim(false))
+            .changed();
+
+        changed
+    }
+}
@@ -0,0 +1,28 @@
+use egui::Ui;
+use serde::{Deserialize, Serialize};
+
+/// How the land mesh is coloured and lit, see
+/// [`crate::geometry::land::IndexedLandGeometry`] for the vertices of each.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LandShading {
+    /// Faceted cells of one colour and one normal each, darkened by a seeded
+    /// jitter so neighbouring cells of the same biome stay distinguishable
+    #[default]
+    Flat,
+    /// Plain biome colours and normals, blending evenly from cell to cell
+    Smooth,
+}
+
+impl LandShading {
+    pub fn render_on(&mut self, ui: &mut Ui) -> bool {
+        let mut changed = false;
+
+        ui.horizontal(|ui| {
+            ui.label("Shading");
+            changed |= ui.radio_value(self, LandShading::Flat, "Flat").changed();
+            changed |= ui.radio_value(self, LandShading::Smooth, "Smooth").changed();
+        });
+
+        changed
+    }
+}
@@ -0,0 +1,145 @@
+use egui::Ui;
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct Wave {
+    /// Height of a crest above the sea level
+    pub amplitude: f32,
+    /// Distance between crests, in planet radii
+    pub wavelength: f32,
+    /// Axis the wave fronts travel along
+    pub direction: [f32; 3],
+    /// Speed of the crests, in planet radii per second
+    pub speed: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WaterWavesWidget {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub waves: Vec<Wave>,
+    /// Most times a second the water is rebuilt while animating. rend3 can't
+    /// rewrite the vertices of a mesh, so every rebuild uploads a new mesh and
+    /// re-adds the water object
+    #[serde(default = "default_update_rate")]
+    pub update_rate: f32,
+}
+
+fn default_update_rate() -> f32 {
+    30.0
+}
+
+impl Default for WaterWavesWidget {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            waves: vec![
+                Wave {
+                    amplitude: 0.002,
+                    wavelength: 0.06,
+                    direction: [1.0, 0.0, 0.0],
+                    speed: 0.01,
+                },
+                Wave {
+                    amplitude: 0.0015,
+                    wavelength: 0.035,
+                    direction: [0.0, 0.3, 1.0],
+                    speed: 0.008,
+                },
+                Wave {
+                    amplitude: 0.001,
+                    wavelength: 0.02,
+                    direction: [-0.6, 0.8, 0.2],
+                    speed: 0.006,
+                },
+            ],
+            update_rate: default_update_rate(),
+        }
+    }
+}
+
+impl WaterWavesWidget {
+    pub fn render_on(&mut self, ui: &mut Ui) -> bool {
+        let mut changed = ui.checkbox(&mut self.enabled, "Animate").changed();
+
+        ui.label("Updates per second");
+        ui.add(egui::Slider::new(&mut self.update_rate, 1.0..=60.0))
+            .on_hover_text("Every update uploads the whole water mesh again");
+
+        let mut remove = None;
+
+        for (idx, wave) in self.waves.iter_mut().enumerate() {
+            ui.collapsing(format!("Wave {}", idx + 1), |ui| {
+                ui.label("Amplitude");
+                changed |= ui
+                    .add(egui::Slider::new(&mut wave.amplitude, 0.0..=0.01).smart_aim(false))
+                    .changed();
+
+                ui.label("Wavelength");
+                changed |= ui
+                    .add(egui::Slider::new(&mut wave.wavelength, 0.005..=0.5).smart_aim(false))
+                    .changed();
+
+                ui.label("Speed");
+                changed |= ui
+                    .add(egui::Slider::new(&mut wave.speed, -0.1..=0.1).smart_aim(false))
+                    .changed();
+
+                ui.label("Direction");
+                ui.horizontal(|ui| {
+                    for component in wave.direction.iter_mut() {
+                        changed |= ui
+                            .add(egui::DragValue::new(component).speed(0.01).clamp_range(-1.0..=1.0))
+                            .changed();
+                    }
+                });
+
+                if ui.button("Remove").clicked() {
+                    remove = Some(idx);
+                }
+            });
+        }
+
+        if let Some(idx) = remove {
+            self.waves.remove(idx);
+            changed = true;
+        }
+
+        if ui.button("Add Wave").clicked() {
+            self.waves.push(Wave {
+                amplitude: 0.001,
+                wavelength: 0.05,
+                direction: [0.0, 1.0, 0.0],
+                speed: 0.01,
+            });
+            changed = true;
+        }
+
+        changed
+    }
+
+    /// Gerstner displacement of a point on the unit sphere, returning the
+    /// moved point scaled out to `sea_level`.
+    pub fn displace(&self, point: Vec3, sea_level: f32, time: f32) -> Vec3 {
+        let mut height = sea_level;
+        let mut offset = Vec3::ZERO;
+
+        for wave in &self.waves {
+            let direction = Vec3::from_array(wave.direction).normalize_or_zero();
+            if direction == Vec3::ZERO || wave.wavelength <= 0.0 {
+                continue;
+            }
+
+            let k = std::f32::consts::TAU / wave.wavelength;
+            let phase = k * (point.dot(direction) - wave.speed * time);
+            let tangent = (direction - point * point.dot(direction)).normalize_or_zero();
+
+            height += wave.amplitude * phase.sin();
+            offset += tangent * (wave.amplitude * phase.cos());
+        }
+
+        point * height + offset
+    }
+}
@@ -2,7 +2,12 @@ use std::ops::RangeInclusive;
 use egui::{DragValue, Ui};
 use serde::{Deserialize, Serialize};
 use sphere_terrain::{AF32, DropSettings, World};
-use crate::widgets::LandOpts;
+use crate::erosion::{ErosionJob, ErosionPoll};
+use crate::snapshot::{cell_count, MAX_SUBDIVISIONS, SNAPSHOT_PATH, WorldSnapshot};
+use crate::util::dirty::DirtyCells;
+use crate::util::history::WorldHistory;
+use crate::util::widgets::climate::{MoistureOpts, TemperatureOpts};
+use crate::util::widgets::noise::LandOpts;
 
 #[derive(Serialize, Deserialize, Default, Debug, Copy, Clone)]
 pub struct WorldGenWidget {
@@ -21,11 +26,33 @@ pub struct WorldGenWidget {
     pub blur_iters: usize,
     #[serde(default)]
     pub blur_coeff: f32,
+    #[serde(default)]
+    pub temperature: TemperatureOpts,
+    #[serde(default)]
+    pub moisture: MoistureOpts,
 }
 
 impl WorldGenWidget {
-    pub fn render_on(&mut self, ui: &mut Ui, world: &mut World) -> bool {
+    /// Marks the cells it edits in `dirty`. Returns true if the climate
+    /// changed, which only recolours the land.
+    pub fn render_on(
+        &mut self,
+        ui: &mut Ui,
+        world: &mut World,
+        history: &mut WorldHistory,
+        erosion: &mut Option<ErosionJob>,
+        dirty: &mut DirtyCells,
+    ) -> bool {
+        if let Some(job) = erosion {
+            job.render_on(ui);
+            ui.separator();
+        }
+        // The worker overwrites the heights when it's done, so nothing else may edit them
+        ui.set_enabled(erosion.is_none());
+
         let mut changed = false;
+        // What happened to the world this frame, and whether it came from a slider
+        let mut action = None;
         ui.collapsing(
             "Noise",
             |ui| {
@@ -66,12 +93,31 @@ impl WorldGenWidget {
             }
         );
 
+        // Only changes colours, so the world is left alone
+        let mut changed_climate = false;
+
+        ui.collapsing(
+            "Temperature",
+            |ui| {
+                changed_climate |= self.temperature.render_on(ui);
+            }
+        );
+
+        ui.collapsing(
+            "Prevailing Winds",
+            |ui| {
+                changed_climate |= self.moisture.render_on(ui);
+            }
+        );
+
         changed |= ui.button("Reset World").clicked();
 
         if changed {
             *world = World::new(self.opts.subdivisions, self.drop_opts.0);
             world.fill_noise_heights(self.opts.into());
             world.fill_hardness(self.hardness_opts.into());
+            dirty.mark_all();
+            action = Some(("Reset World".to_string(), true));
         } else if changed_drops {
             world.settings = self.drop_opts.0;
         }
@@ -82,11 +128,9 @@ impl WorldGenWidget {
         ui.label("Number of blur passes");
         ui.add(DragValue::new(&mut self.blur_iters));
 
-        if ui.button(format!("Run {} Drops", self.num_iters)).clicked() {
+        if ui.button(format!("Run {} Drops", self.num_iters)).clicked() && self.num_iters != 0 {
             println!("Simulating {} drops", self.num_iters);
-            world.simulate_node_centered_drops(self.num_iters, self.blur_iters);
-
-            changed |= self.num_iters != 0;
+            *erosion = Some(ErosionJob::spawn(*self, world, self.num_iters, self.blur_iters));
         }
 
         ui.label("Blurred Coefficient");
@@ -94,6 +138,7 @@ impl WorldGenWidget {
             .changed();
 
         if ui.button(format!("Blur world {:.1}/{:.1}", 100.0 * self.blur_coeff, 100.0 * (1.0 - self.blur_coeff))).clicked() {
+            let before = world.heights.iter().map(|x| x.load()).collect::<Vec<_>>();
             let blurred = vec![AF32::new(0.0); world.positions.len()];
             world.blur_apply(&world.heights, &blurred);
 
@@ -106,16 +151,119 @@ impl WorldGenWidget {
                     let blurred = blurred.load();
                     into.store(blurred * self.blur_coeff + orig * (1.0 - self.blur_coeff));
                 });
+            dirty.mark_changed(before, world.heights.iter().map(|x| x.load()));
+            // Reshapes the land as a whole, so the rain has to be worked out again
+            dirty.mark_rain();
 
             changed = true;
+            action = Some((format!("Blur {:.1}%", 100.0 * self.blur_coeff), false));
         }
 
 
         if changed_wetness_drops || changed {
-            world.fill_wetness(self.wetness_evaporation, self.wetness_inertia, self.river_steps);
+            self.fill_wetness(world, dirty);
+            action.get_or_insert_with(|| ("Wetness".to_string(), true));
         }
 
-        changed || changed_drops
+        ui.horizontal(|ui| {
+            if ui.button("Save Snapshot").clicked() {
+                match WorldSnapshot::capture(world, self.opts.subdivisions).save(SNAPSHOT_PATH) {
+                    Ok(()) => println!("Saved {}", SNAPSHOT_PATH),
+                    Err(e) => println!("Could not save {}: {}", SNAPSHOT_PATH, e),
+                }
+            }
+
+            if ui.button("Load Snapshot").clicked() {
+                let restored = WorldSnapshot::load(SNAPSHOT_PATH)
+                    .map_err(|e| e.to_string())
+                    .and_then(|snapshot| self.restore(&snapshot));
+                match restored {
+                    Ok(restored) => {
+                        *world = restored;
+                        dirty.mark_all();
+                        action = Some(("Load Snapshot".to_string(), false));
+                    }
+                    Err(e) => println!("Could not load {}: {}", SNAPSHOT_PATH, e),
+                }
+            }
+        });
+
+        if let Some((label, merge)) = action {
+            history.record(label, world, merge);
+        }
+
+        ui.collapsing(
+            "History",
+            |ui| {
+                history.render_on(ui, world, dirty);
+            }
+        );
+
+        changed_climate
+    }
+
+    /// Refills the wetness from the heights, marking the cells it changed.
+    fn fill_wetness(&self, world: &World, dirty: &mut DirtyCells) {
+        let before = world.wetness.iter().map(|x| x.load()).collect::<Vec<_>>();
+        world.fill_wetness(self.wetness_evaporation, self.wetness_inertia, self.river_steps);
+        dirty.mark_changed(before, world.wetness.iter().map(|x| x.load()));
+    }
+
+    /// Applies whatever the erosion worker has published, finishing the job
+    /// once it is done. The cells it changed are marked in `dirty`.
+    pub fn poll_erosion(&self, world: &World, history: &mut WorldHistory, erosion: &mut Option<ErosionJob>, dirty: &mut DirtyCells) {
+        let job = match erosion {
+            Some(job) => job,
+            None => return,
+        };
+
+        let label = match job.poll(world, dirty) {
+            ErosionPoll::Idle | ErosionPoll::Updated => return,
+            ErosionPoll::Finished { cancelled: false } => format!("Run {} Drops", job.total),
+            ErosionPoll::Finished { cancelled: true } => format!("Run {}/{} Drops", job.done, job.total),
+            // Already logged, and the heights published before it failed are kept
+            ErosionPoll::Failed(_) => format!("Run {}/{} Drops (failed)", job.done, job.total),
+        };
+        *erosion = None;
+
+        self.fill_wetness(world, dirty);
+        // The published chunks kept the old rain, so work it out again
+        dirty.mark_rain();
+        history.record(label, world, false);
+    }
+
+    /// Rebuilds a `World` from a snapshot without running any noise, adopting
+    /// its subdivisions.
+    pub fn restore(&mut self, snapshot: &WorldSnapshot) -> Result<World, String> {
+        // Checked before building the world, which allocates for every cell
+        let cells = snapshot.heights.len();
+        let consistent = snapshot.subdivisions <= MAX_SUBDIVISIONS
+            && cell_count(snapshot.subdivisions) == Some(cells)
+            && snapshot.hardness.len() == cells
+            && snapshot.wetness.len() == cells;
+        if !consistent {
+            return Err(format!(
+                "snapshot with {} subdivisions has {} heights, {} hardness and {} wetness values",
+                snapshot.subdivisions,
+                cells,
+                snapshot.hardness.len(),
+                snapshot.wetness.len(),
+            ));
+        }
+
+        let world = World::new(snapshot.subdivisions, self.drop_opts.0);
+
+        let fill = |into: &[AF32], from: &[f32]| {
+            into.iter().zip(from.iter()).for_each(|(into, &from)| into.store(from));
+        };
+
+        fill(&world.heights, &snapshot.heights);
+        fill(&world.hardness, &snapshot.hardness);
+        fill(&world.wetness, &snapshot.wetness);
+
+        self.opts.subdivisions = snapshot.subdivisions;
+
+        Ok(world)
     }
 
     pub fn get_state(&self) -> World {